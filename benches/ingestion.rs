@@ -0,0 +1,79 @@
+//! Measures how fast transforms can be inserted into a `TfBuffer`.
+//!
+//! Each iteration ingests one second worth of data at 10k transforms/s, spread over a number of 
+//! edges hanging off a common root frame. Run with `cargo bench`.
+extern crate criterion;
+extern crate rosrust;
+extern crate rustros_tf;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rustros_tf::msg;
+use rustros_tf::TfBuffer;
+
+const TRANSFORMS_PER_SECOND: u32 = 10_000;
+
+fn make_transform(child: usize, nanos: u32) -> msg::geometry_msgs::TransformStamped {
+    msg::geometry_msgs::TransformStamped {
+        child_frame_id: format!("link_{}", child),
+        header: msg::std_msgs::Header {
+            frame_id: "base_link".to_string(),
+            stamp: rosrust::Time{sec: 1, nsec: nanos},
+            seq: 0
+        },
+        transform: msg::geometry_msgs::Transform{
+            rotation: msg::geometry_msgs::Quaternion{
+                x: 0f64, y: 0f64, z: 0f64, w: 1f64
+            },
+            translation: msg::geometry_msgs::Vector3{
+                x: child as f64, y: f64::from(nanos) * 1e-9, z: 0f64
+            }
+        }
+    }
+}
+
+/// Builds one second of transforms published round robin on `edges` edges.
+fn in_order_stream(edges: usize) -> Vec<msg::geometry_msgs::TransformStamped> {
+    let step = 1_000_000_000 / TRANSFORMS_PER_SECOND;
+    (0..TRANSFORMS_PER_SECOND).map(|i| make_transform(i as usize % edges, i * step)).collect()
+}
+
+/// Same as `in_order_stream` but every pair of consecutive messages on an edge is swapped.
+fn out_of_order_stream(edges: usize) -> Vec<msg::geometry_msgs::TransformStamped> {
+    let mut stream = in_order_stream(edges);
+    let mut i = 0;
+    while i + edges < stream.len() {
+        stream.swap(i, i + edges);
+        i += 2 * edges;
+    }
+    stream
+}
+
+fn bench_ingestion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ingestion");
+    group.throughput(Throughput::Elements(u64::from(TRANSFORMS_PER_SECOND)));
+    for edges in [10usize, 100, 500].iter() {
+        let stream = in_order_stream(*edges);
+        group.bench_with_input(BenchmarkId::new("in_order", edges), &stream, |b, stream| {
+            b.iter_batched(TfBuffer::new, |mut buffer| {
+                for transform in stream {
+                    buffer.set_transform(transform, "bench", false).unwrap();
+                }
+                buffer
+            }, BatchSize::LargeInput)
+        });
+
+        let stream = out_of_order_stream(*edges);
+        group.bench_with_input(BenchmarkId::new("out_of_order", edges), &stream, |b, stream| {
+            b.iter_batched(TfBuffer::new, |mut buffer| {
+                for transform in stream {
+                    buffer.set_transform(transform, "bench", false).unwrap();
+                }
+                buffer
+            }, BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ingestion);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Measures how fast transforms can be looked up in a `TfBuffer`.
+//!
+//! The tree is made of a few long kinematic chains hanging off a common root, similar to a robot 
+//! with several arms. Run with `cargo bench`.
+extern crate criterion;
+extern crate rosrust;
+extern crate rustros_tf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustros_tf::msg;
+use rustros_tf::TfBuffer;
+
+const CHAINS: usize = 10;
+const CHAIN_LENGTH: usize = 20;
+
+fn make_transform(parent: &str, child: &str, sec: u32) -> msg::geometry_msgs::TransformStamped {
+    msg::geometry_msgs::TransformStamped {
+        child_frame_id: child.to_string(),
+        header: msg::std_msgs::Header {
+            frame_id: parent.to_string(),
+            stamp: rosrust::Time{sec, nsec: 0},
+            seq: 0
+        },
+        transform: msg::geometry_msgs::Transform{
+            rotation: msg::geometry_msgs::Quaternion{
+                x: 0f64, y: 0f64, z: 0f64, w: 1f64
+            },
+            translation: msg::geometry_msgs::Vector3{
+                x: 0.1f64, y: f64::from(sec), z: 0f64
+            }
+        }
+    }
+}
+
+/// Builds a tree of `CHAINS * CHAIN_LENGTH` frames with two samples on every edge.
+fn build_tree() -> TfBuffer {
+    let mut buffer = TfBuffer::new();
+    for sec in 1..3 {
+        for chain in 0..CHAINS {
+            let mut parent = "base_link".to_string();
+            for link in 0..CHAIN_LENGTH {
+                let child = format!("chain_{}_link_{}", chain, link);
+                buffer.set_transform(&make_transform(&parent, &child, sec), "bench", false).unwrap();
+                parent = child;
+            }
+        }
+    }
+    buffer
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let buffer = build_tree();
+    let time = rosrust::Time{sec: 1, nsec: 500_000_000};
+    let from = format!("chain_0_link_{}", CHAIN_LENGTH - 1);
+    let to = format!("chain_{}_link_{}", CHAINS - 1, CHAIN_LENGTH - 1);
+    c.bench_function("lookup_across_chains", |b| {
+        b.iter(|| buffer.lookup_transform(&from, &to, time).unwrap())
+    });
+    let to = format!("chain_0_link_{}", CHAIN_LENGTH / 2);
+    c.bench_function("lookup_within_chain", |b| {
+        b.iter(|| buffer.lookup_transform(&from, &to, time).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_lookup);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+//! Command line handling shared by the binaries.
+
+/// Returns the command line arguments without the program name. ROS remappings like `__name:=tf_echo` are left
+/// out, `rosrust::init` picks them up itself.
+pub fn command_line_args() -> Vec<String> {
+    std::env::args().skip(1).filter(|arg| !arg.contains(":=")).collect()
+}
+
+/// Splits a command line on whitespace, for tests.
+#[cfg(test)]
+pub fn args(s: &str) -> Vec<String> {
+    s.split_whitespace().map(String::from).collect()
+}
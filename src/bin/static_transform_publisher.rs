@@ -0,0 +1,264 @@
+//! Publishes static transforms on `/tf_static`, like `static_transform_publisher` from `tf2_ros`.
+//!
+//! ```text
+//! static_transform_publisher x y z yaw pitch roll frame_id child_frame_id
+//! static_transform_publisher x y z qx qy qz qw frame_id child_frame_id
+//! static_transform_publisher frames.yaml
+//! ```
+//!
+//! Angles are in radians and the rotation is applied as yaw about Z, then pitch about Y, then roll about X.
+//! The YAML file holds a list of static frames:
+//!
+//! ```yaml
+//! - frame_id: world
+//!   child_frame_id: base_link
+//!   translation: {x: 0.0, y: 0.0, z: 0.5}
+//!   rotation: {x: 0.0, y: 0.0, z: 0.0, w: 1.0}
+//! - frame_id: base_link
+//!   child_frame_id: camera
+//!   translation: {x: 0.1, y: 0.0, z: 0.2}
+//!   rotation: {yaw: 1.57, pitch: 0.0, roll: 0.0}
+//! ```
+use std::process;
+
+use rustros_tf::{msg, Position, Quaternion, StaticTfBroadcaster, Transform};
+use yaml_rust::{Yaml, YamlLoader};
+
+mod common;
+
+const USAGE: &str = "Usage:
+  static_transform_publisher x y z yaw pitch roll frame_id child_frame_id
+  static_transform_publisher x y z qx qy qz qw frame_id child_frame_id
+  static_transform_publisher frames.yaml";
+
+/// How far the norm of a quaternion may be from one before it is rejected.
+const QUATERNION_NORM_TOLERANCE: f64 = 1e-3;
+
+/// A transform from `parent` to `child` which does not change over time.
+#[derive(Debug)]
+struct StaticFrame {
+    parent: String,
+    child: String,
+    transform: Transform
+}
+
+fn main() {
+    let args = common::command_line_args();
+    let frames = match parse_args(&args) {
+        Ok(frames) => frames,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    // Like tf2_ros, use an anonymous node name so that several publishers can run side by side
+    rosrust::init(&format!("static_transform_publisher_{}", process::id()));
+    let mut broadcaster = StaticTfBroadcaster::new();
+    // Send all frames in a single latched message
+    let now = rosrust::now();
+    if let Err(e) = broadcaster.send_transforms(frames.iter().map(|frame| frame.to_message(now)).collect()) {
+        eprintln!("Failed to publish the static transforms: {}", e);
+        process::exit(1);
+    }
+    for frame in &frames {
+        rosrust::ros_info!("Publishing static transform from {} to {}", frame.parent, frame.child);
+    }
+    rosrust::spin();
+}
+
+impl StaticFrame {
+    /// The transform stamped with `time`.
+    fn to_message(&self, time: rosrust::Time) -> msg::geometry_msgs::TransformStamped {
+        let (p, q) = (&self.transform.position, &self.transform.orientation);
+        msg::geometry_msgs::TransformStamped {
+            child_frame_id: self.child.clone(),
+            header: msg::std_msgs::Header {
+                frame_id: self.parent.clone(),
+                stamp: time,
+                seq: 0
+            },
+            transform: msg::geometry_msgs::Transform{
+                rotation: msg::geometry_msgs::Quaternion{x: q.x, y: q.y, z: q.z, w: q.w},
+                translation: msg::geometry_msgs::Vector3{x: p.x, y: p.y, z: p.z}
+            }
+        }
+    }
+}
+
+/// Parses the command line, without the program name, into the frames to publish.
+fn parse_args(args: &[String]) -> Result<Vec<StaticFrame>, String> {
+    match args.len() {
+        1 => {
+            let text = std::fs::read_to_string(&args[0])
+                .map_err(|e| format!("Could not read {}: {}", args[0], e))?;
+            parse_yaml(&text)
+        },
+        8 => {
+            let v = parse_numbers(&args[..6])?;
+            let orientation = from_yaw_pitch_roll(v[3], v[4], v[5]);
+            Ok(vec![static_frame(&args[6], &args[7], [v[0], v[1], v[2]], orientation)?])
+        },
+        9 => {
+            let v = parse_numbers(&args[..7])?;
+            let orientation = checked_quaternion(v[3], v[4], v[5], v[6])?;
+            Ok(vec![static_frame(&args[7], &args[8], [v[0], v[1], v[2]], orientation)?])
+        },
+        n => Err(format!("Expected 1, 8 or 9 arguments but got {}", n))
+    }
+}
+
+fn parse_numbers(args: &[String]) -> Result<Vec<f64>, String> {
+    args.iter()
+        .map(|arg| arg.parse::<f64>().map_err(|_| format!("{} is not a number", arg)))
+        .collect()
+}
+
+fn static_frame(parent: &str, child: &str, position: [f64; 3], orientation: Quaternion) -> Result<StaticFrame, String> {
+    if parent == child {
+        return Err(format!("The parent and child frames must differ, but both are {}", parent));
+    }
+    if parent.is_empty() || child.is_empty() {
+        return Err("Frame names must not be empty".to_string());
+    }
+    Ok(StaticFrame{
+        parent: parent.to_string(),
+        child: child.to_string(),
+        transform: Transform{
+            orientation,
+            position: Position{x: position[0], y: position[1], z: position[2]}
+        }
+    })
+}
+
+/// Converts the angles in radians to a quaternion which first rotates by yaw about Z, then by pitch about Y and
+/// finally by roll about X.
+fn from_yaw_pitch_roll(yaw: f64, pitch: f64, roll: f64) -> Quaternion {
+    let q = nalgebra::UnitQuaternion::from_euler_angles(roll, pitch, yaw);
+    Quaternion{x: q.i, y: q.j, z: q.k, w: q.w}
+}
+
+/// Rejects quaternions which do not describe a rotation. Quaternions which are slightly off from unit length
+/// are normalized.
+fn checked_quaternion(x: f64, y: f64, z: f64, w: f64) -> Result<Quaternion, String> {
+    let norm = (x*x + y*y + z*z + w*w).sqrt();
+    if !norm.is_finite() || (norm - 1f64).abs() > QUATERNION_NORM_TOLERANCE {
+        return Err(format!("The quaternion ({}, {}, {}, {}) is not normalized, its norm is {}", x, y, z, w, norm));
+    }
+    Ok(Quaternion{x: x/norm, y: y/norm, z: z/norm, w: w/norm})
+}
+
+/// Parses a YAML list of static frames.
+fn parse_yaml(text: &str) -> Result<Vec<StaticFrame>, String> {
+    let docs = YamlLoader::load_from_str(text).map_err(|e| format!("Invalid YAML: {}", e))?;
+    let entries = match docs.first().and_then(|doc| doc.as_vec()) {
+        Some(entries) => entries,
+        None => return Err("The YAML file must contain a list of frames".to_string())
+    };
+    entries.iter().enumerate()
+        .map(|(i, entry)| parse_yaml_frame(entry).map_err(|e| format!("Frame {}: {}", i, e)))
+        .collect()
+}
+
+fn parse_yaml_frame(entry: &Yaml) -> Result<StaticFrame, String> {
+    let parent = yaml_str(entry, "frame_id")?;
+    let child = yaml_str(entry, "child_frame_id")?;
+    let translation = &entry["translation"];
+    let position = if translation.is_badvalue() {
+        [0f64; 3]
+    } else {
+        [yaml_f64(translation, "x")?, yaml_f64(translation, "y")?, yaml_f64(translation, "z")?]
+    };
+    let rotation = &entry["rotation"];
+    let orientation = if rotation.is_badvalue() {
+        Quaternion{x: 0f64, y: 0f64, z: 0f64, w: 1f64}
+    } else if !rotation["w"].is_badvalue() {
+        checked_quaternion(yaml_f64(rotation, "x")?, yaml_f64(rotation, "y")?, yaml_f64(rotation, "z")?, yaml_f64(rotation, "w")?)?
+    } else {
+        from_yaw_pitch_roll(yaml_f64(rotation, "yaw")?, yaml_f64(rotation, "pitch")?, yaml_f64(rotation, "roll")?)
+    };
+    static_frame(parent, child, position, orientation)
+}
+
+fn yaml_str<'a>(entry: &'a Yaml, key: &str) -> Result<&'a str, String> {
+    entry[key].as_str().ok_or_else(|| format!("{} must be a string", key))
+}
+
+fn yaml_f64(entry: &Yaml, key: &str) -> Result<f64, String> {
+    let value = &entry[key];
+    value.as_f64()
+        .or_else(|| value.as_i64().map(|x| x as f64))
+        .ok_or_else(|| format!("{} must be a number", key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::args;
+
+    #[test]
+    fn test_parse_quaternion_args() {
+        let frames = parse_args(&args("1 2 3 0 0 0 1 world base_link")).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].parent, "world");
+        assert_eq!(frames[0].child, "base_link");
+        assert_eq!(frames[0].transform.position, Position{x: 1f64, y: 2f64, z: 3f64});
+        assert_eq!(frames[0].transform.orientation.w, 1f64);
+
+        assert!(parse_args(&args("1 2 3 0 0 0 2 world base_link")).is_err());
+        assert!(parse_args(&args("1 2 3 0 0 0 0 world base_link")).is_err());
+        assert!(parse_args(&args("1 2 3 0 0 0 1 world world")).is_err());
+        assert!(parse_args(&args("1 2 a 0 0 0 1 world base_link")).is_err());
+        assert!(parse_args(&args("1 2 3 world base_link")).is_err());
+    }
+
+    #[test]
+    fn test_parse_yaw_pitch_roll_args() {
+        let frames = parse_args(&args("0 0 0 1.5707963267948966 0 0 world base_link")).unwrap();
+        let q = frames[0].transform.orientation;
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert!(q.x.abs() < 1e-9 && q.y.abs() < 1e-9);
+        assert!((q.z - half).abs() < 1e-9 && (q.w - half).abs() < 1e-9);
+
+        // Pitch is applied after yaw, about the rotated Y axis
+        let q = from_yaw_pitch_roll(0.3, 0.2, 0.1);
+        let expected = nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::z_axis(), 0.3)
+            * nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::y_axis(), 0.2)
+            * nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::x_axis(), 0.1);
+        assert!((q.x - expected.i).abs() < 1e-9 && (q.y - expected.j).abs() < 1e-9);
+        assert!((q.z - expected.k).abs() < 1e-9 && (q.w - expected.w).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let text = "
+- frame_id: world
+  child_frame_id: base_link
+  translation: {x: 0, y: 0, z: 0.5}
+  rotation: {x: 0.0, y: 0.0, z: 0.0, w: 1.0}
+- frame_id: base_link
+  child_frame_id: camera
+  rotation: {yaw: 0.0, pitch: 0.0, roll: 0.0}
+";
+        let frames = parse_yaml(text).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].transform.position.z, 0.5f64);
+        assert_eq!(frames[1].parent, "base_link");
+        assert_eq!(frames[1].transform.position.x, 0f64);
+        assert!((frames[1].transform.orientation.w - 1f64).abs() < 1e-9);
+
+        assert!(parse_yaml("frame_id: world").is_err());
+        assert!(parse_yaml("- frame_id: world").is_err());
+        assert!(parse_yaml("- {frame_id: a, child_frame_id: b, rotation: {x: 1, y: 1, z: 0, w: 0}}").is_err());
+    }
+
+    #[test]
+    fn test_to_message() {
+        let frames = parse_args(&args("1 2 3 0 0 0 1 world base_link")).unwrap();
+        let tf = frames[0].to_message(rosrust::Time{sec: 5, nsec: 0});
+        assert_eq!((tf.header.frame_id.as_str(), tf.child_frame_id.as_str()), ("world", "base_link"));
+        assert_eq!(tf.header.stamp, rosrust::Time{sec: 5, nsec: 0});
+        assert_eq!((tf.transform.translation.x, tf.transform.translation.y, tf.transform.translation.z), (1f64, 2f64, 3f64));
+        assert_eq!(tf.transform.rotation.w, 1f64);
+    }
+}
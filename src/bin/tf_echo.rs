@@ -0,0 +1,139 @@
+//! Prints the transform between two frames, like `tf_echo` from `tf`.
+//!
+//! ```text
+//! tf_echo source_frame target_frame [echo_rate]
+//! ```
+//!
+//! The transform is the pose of `target_frame` expressed in `source_frame` at the latest time both are
+//! available, printed `echo_rate` times per second (once per second by default).
+use std::fmt::Write;
+use std::process;
+
+use rustros_tf::{msg, TfListener};
+
+mod common;
+
+const USAGE: &str = "Usage: tf_echo source_frame target_frame [echo_rate]";
+
+fn main() {
+    let args = common::command_line_args();
+    let (source, target, rate) = match parse_args(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    rosrust::init(&format!("tf_echo_{}", process::id()));
+    let listener = TfListener::new();
+    let rate = rosrust::rate(rate);
+    while rosrust::is_ok() {
+        rate.sleep();
+        match listener.lookup_transform(&source, &target, rosrust::Time::new()) {
+            Ok(tf) => print!("{}", format_transform(&tf, rosrust::now())),
+            Err(e) => println!("Failure at {}\n{}", format_time(rosrust::now()), e)
+        }
+    }
+}
+
+/// Parses the command line, without the program name, into the source frame, target frame and echo rate.
+fn parse_args(args: &[String]) -> Result<(String, String, f64), String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(format!("Expected 2 or 3 arguments but got {}", args.len()));
+    }
+    let rate = match args.get(2) {
+        Some(rate) => rate.parse::<f64>().map_err(|_| format!("{} is not a number", rate))?,
+        None => 1f64
+    };
+    if !rate.is_finite() || rate <= 0f64 {
+        return Err(format!("The echo rate must be positive, but is {}", rate));
+    }
+    Ok((args[0].clone(), args[1].clone(), rate))
+}
+
+fn format_time(time: rosrust::Time) -> String {
+    format!("{}.{:09}", time.sec, time.nsec)
+}
+
+/// Formats the values to three decimals. Values which round to zero are printed without a sign.
+fn format_values(values: &[f64], separator: &str) -> String {
+    values.iter()
+        .map(|x| if x.abs() < 5e-4 { 0f64 } else { *x })
+        .map(|x| format!("{:.3}", x))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Formats the transform for printing, including how old it is at `now`. Transforms between frames connected only
+/// by static transforms are stamped zero, so they have no age.
+fn format_transform(tf: &msg::geometry_msgs::TransformStamped, now: rosrust::Time) -> String {
+    let t = &tf.transform.translation;
+    let r = &tf.transform.rotation;
+    let rotation = nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(r.w, r.x, r.y, r.z));
+    let (roll, pitch, yaw) = rotation.euler_angles();
+    let matrix = nalgebra::Isometry3::from_parts(nalgebra::Translation3::new(t.x, t.y, t.z), rotation).to_homogeneous();
+
+    let mut out = String::new();
+    writeln!(out, "At time {} ({} -> {})", format_time(tf.header.stamp), tf.header.frame_id, tf.child_frame_id).unwrap();
+    if tf.header.stamp != rosrust::Time::new() {
+        let age = now - tf.header.stamp;
+        writeln!(out, "- Age: {:.3} s", f64::from(age.sec) + f64::from(age.nsec) * 1e-9).unwrap();
+    }
+    writeln!(out, "- Translation: [{}]", format_values(&[t.x, t.y, t.z], ", ")).unwrap();
+    writeln!(out, "- Rotation: in Quaternion [{}]", format_values(&[r.x, r.y, r.z, r.w], ", ")).unwrap();
+    writeln!(out, "            in RPY (radian) [{}]", format_values(&[roll, pitch, yaw], ", ")).unwrap();
+    writeln!(out, "            in RPY (degree) [{}]", format_values(&[roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees()], ", ")).unwrap();
+    writeln!(out, "- Matrix:").unwrap();
+    for row in matrix.row_iter() {
+        let row: Vec<f64> = row.iter().cloned().collect();
+        writeln!(out, "  {}", format_values(&row, " ")).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::args;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&args("world base_link")).unwrap(), ("world".to_string(), "base_link".to_string(), 1f64));
+        assert_eq!(parse_args(&args("world base_link 10")).unwrap().2, 10f64);
+        assert!(parse_args(&args("world")).is_err());
+        assert!(parse_args(&args("world base_link 0")).is_err());
+        assert!(parse_args(&args("world base_link fast")).is_err());
+    }
+
+    #[test]
+    fn test_format_transform() {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let tf = msg::geometry_msgs::TransformStamped {
+            child_frame_id: "base_link".to_string(),
+            header: msg::std_msgs::Header {
+                frame_id: "world".to_string(),
+                stamp: rosrust::Time{sec: 10, nsec: 0},
+                seq: 0
+            },
+            transform: msg::geometry_msgs::Transform{
+                rotation: msg::geometry_msgs::Quaternion{x: 0f64, y: 0f64, z: half, w: half},
+                translation: msg::geometry_msgs::Vector3{x: 1f64, y: 2f64, z: 3f64}
+            }
+        };
+        let out = format_transform(&tf, rosrust::Time{sec: 10, nsec: 250_000_000});
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "At time 10.000000000 (world -> base_link)");
+        assert_eq!(lines[1], "- Age: 0.250 s");
+        assert_eq!(lines[2], "- Translation: [1.000, 2.000, 3.000]");
+        assert_eq!(lines[3], "- Rotation: in Quaternion [0.000, 0.000, 0.707, 0.707]");
+        assert_eq!(lines[4], "            in RPY (radian) [0.000, 0.000, 1.571]");
+        assert_eq!(lines[5], "            in RPY (degree) [0.000, 0.000, 90.000]");
+        assert_eq!(lines[7], "  0.000 -1.000 0.000 1.000");
+        assert_eq!(lines[10], "  0.000 0.000 0.000 1.000");
+
+        let static_tf = msg::geometry_msgs::TransformStamped{header: msg::std_msgs::Header{stamp: rosrust::Time::new(), ..tf.header}, ..tf};
+        let out = format_transform(&static_tf, rosrust::Time{sec: 10, nsec: 0});
+        assert!(out.starts_with("At time 0.000000000 (world -> base_link)\n- Translation: [1.000, 2.000, 3.000]\n"));
+    }
+}
@@ -0,0 +1,238 @@
+//! Reports how often transforms are published and how late they arrive, like `tf_monitor` from `tf`.
+//!
+//! ```text
+//! tf_monitor [source_frame target_frame] [--period seconds]
+//! ```
+//!
+//! Every period (one second by default) the rate and delay of every edge and of every broadcasting node is
+//! printed. The delay is the time between the stamp of a transform and when it was received. If two frames are
+//! given, the delays of the edges on the chain between them are also added up.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::process;
+
+use rustros_tf::{TfEdgeInfo, TfListener, TransformHop};
+
+mod common;
+
+const USAGE: &str = "Usage: tf_monitor [source_frame target_frame] [--period seconds]";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    chain: Option<(String, String)>,
+    period: f64
+}
+
+/// Statistics of all the dynamic edges published by one node.
+#[derive(Debug, PartialEq)]
+struct AuthorityStats {
+    authority: String,
+    /// Sum of the average rates of the edges
+    rate: f64,
+    /// Average delay in seconds, weighted by the number of transforms on each edge
+    average_delay: Option<f64>,
+    max_delay: Option<f64>
+}
+
+fn main() {
+    let args = common::command_line_args();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    rosrust::init(&format!("tf_monitor_{}", process::id()));
+    let listener = TfListener::new();
+    let rate = rosrust::rate(1f64 / options.period);
+    while rosrust::is_ok() {
+        rate.sleep();
+        let edges = listener.edges();
+        print!("{}", format_edges(&edges));
+        if let Some((ref source, ref target)) = options.chain {
+            println!("\nRESULTS: for {} to {}", source, target);
+            match listener.lookup_transform_detailed(source, target, rosrust::Time::new()) {
+                Ok(res) => print!("{}", format_chain(source, &res.hops, &edges)),
+                Err(e) => println!("{}", e)
+            }
+        }
+        println!();
+    }
+}
+
+/// Parses the command line, without the program name.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options{chain: None, period: 1f64};
+    let mut frames = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--period" {
+            let value = args.next().ok_or_else(|| "--period needs a value".to_string())?;
+            options.period = value.parse().map_err(|_| format!("{} is not a number", value))?;
+            if !options.period.is_finite() || options.period <= 0f64 {
+                return Err(format!("The period must be positive, but is {}", value));
+            }
+        } else {
+            frames.push(arg.clone());
+        }
+    }
+    match frames.len() {
+        0 => {},
+        2 => options.chain = Some((frames[0].clone(), frames[1].clone())),
+        n => return Err(format!("Expected 0 or 2 frames but got {}", n))
+    }
+    Ok(options)
+}
+
+fn seconds(delay: Option<rosrust::Duration>) -> Option<f64> {
+    delay.map(|delay| delay.seconds())
+}
+
+fn format_delay(delay: Option<f64>) -> String {
+    match delay {
+        Some(delay) => format!("{:.3}", delay),
+        None => "unknown".to_string()
+    }
+}
+
+/// Groups the dynamic edges by the node publishing them.
+fn authority_stats(edges: &[TfEdgeInfo]) -> Vec<AuthorityStats> {
+    let mut by_authority: BTreeMap<&str, Vec<&TfEdgeInfo>> = BTreeMap::new();
+    for edge in edges.iter().filter(|edge| !edge.static_tf) {
+        by_authority.entry(&edge.authority).or_default().push(edge);
+    }
+    by_authority.into_iter().map(|(authority, edges)| {
+        let delayed: Vec<&&TfEdgeInfo> = edges.iter().filter(|edge| edge.average_delay.is_some()).collect();
+        let samples: usize = delayed.iter().map(|edge| edge.samples).sum();
+        let average_delay = if samples == 0 {
+            None
+        } else {
+            Some(delayed.iter().map(|edge| edge.average_delay.unwrap().seconds() * edge.samples as f64).sum::<f64>() / samples as f64)
+        };
+        AuthorityStats {
+            authority: authority.to_string(),
+            rate: edges.iter().map(|edge| edge.average_rate()).sum(),
+            average_delay,
+            max_delay: delayed.iter().filter_map(|edge| seconds(edge.max_delay)).reduce(f64::max)
+        }
+    }).collect()
+}
+
+/// Formats the statistics of every edge and every broadcasting node.
+fn format_edges(edges: &[TfEdgeInfo]) -> String {
+    let mut out = String::new();
+    writeln!(out, "RESULTS: for all Frames\n\nFrames:").unwrap();
+    for edge in edges {
+        if edge.static_tf {
+            writeln!(out, "Frame: {}, parent: {}, published by {}, static", edge.child, edge.parent, edge.authority).unwrap();
+        } else {
+            writeln!(out, "Frame: {}, parent: {}, published by {}, Average Delay: {}, Max Delay: {}, Average Rate: {:.3} Hz",
+                edge.child, edge.parent, edge.authority, format_delay(seconds(edge.average_delay)), format_delay(seconds(edge.max_delay)),
+                edge.average_rate()).unwrap();
+        }
+    }
+    writeln!(out, "\nAll Broadcasters:").unwrap();
+    for stats in authority_stats(edges) {
+        writeln!(out, "Node: {}, {:.3} Hz, Average Delay: {}, Max Delay: {}",
+            stats.authority, stats.rate, format_delay(stats.average_delay), format_delay(stats.max_delay)).unwrap();
+    }
+    out
+}
+
+/// Formats the chain of frames starting at `source` along `hops` and the delays accumulated along it.
+fn format_chain(source: &str, hops: &[TransformHop], edges: &[TfEdgeInfo]) -> String {
+    let mut frames = vec![source];
+    let mut average = 0f64;
+    let mut max = 0f64;
+    for hop in hops {
+        frames.push(if hop.inverted { &hop.parent } else { &hop.child });
+        let edge = edges.iter().find(|edge| edge.parent == hop.parent && edge.child == hop.child);
+        if let Some(edge) = edge {
+            average += seconds(edge.average_delay).unwrap_or(0f64);
+            max += seconds(edge.max_delay).unwrap_or(0f64);
+        }
+    }
+    format!("Chain is: {}\nNet delay     avg = {:.3}: max = {:.3}\n", frames.join(" -> "), average, max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::args;
+
+    fn edge(parent: &str, child: &str, authority: &str, static_tf: bool, samples: usize, delays: Option<(f64, f64)>) -> TfEdgeInfo {
+        let duration = |x: f64| rosrust::Duration::from_nanos((x * 1e9) as i64);
+        TfEdgeInfo {
+            parent: parent.to_string(),
+            child: child.to_string(),
+            authority: authority.to_string(),
+            static_tf,
+            samples,
+            oldest: rosrust::Time{sec: 0, nsec: 0},
+            latest: rosrust::Time{sec: 1, nsec: 0},
+            average_delay: delays.map(|x| duration(x.0)),
+            max_delay: delays.map(|x| duration(x.1))
+        }
+    }
+
+    fn hop(parent: &str, child: &str, inverted: bool) -> TransformHop {
+        TransformHop {
+            parent: parent.to_string(),
+            child: child.to_string(),
+            inverted,
+            before: rosrust::Time::new(),
+            after: rosrust::Time::new(),
+            weight: 0f64,
+            before_authority: String::new(),
+            after_authority: String::new(),
+            static_tf: false,
+            extrapolation: rustros_tf::ExtrapolationPolicy::Strict
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&args("")).unwrap(), Options{chain: None, period: 1f64});
+        assert_eq!(parse_args(&args("world camera --period 2")).unwrap(),
+            Options{chain: Some(("world".to_string(), "camera".to_string())), period: 2f64});
+        assert!(parse_args(&args("world")).is_err());
+        assert!(parse_args(&args("--period 0")).is_err());
+        assert!(parse_args(&args("--period")).is_err());
+    }
+
+    #[test]
+    fn test_authority_stats() {
+        let edges = vec![
+            edge("world", "base_link", "a", false, 10, Some((0.01, 0.02))),
+            edge("base_link", "arm", "a", false, 30, Some((0.03, 0.05))),
+            edge("base_link", "camera", "a", true, 1, None),
+            edge("world", "target", "b", false, 5, None)
+        ];
+        let stats = authority_stats(&edges);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].authority, "a");
+        assert!((stats[0].rate - 40f64).abs() < 1e-9);
+        assert!((stats[0].average_delay.unwrap() - 0.025).abs() < 1e-9);
+        assert!((stats[0].max_delay.unwrap() - 0.05).abs() < 1e-9);
+        assert_eq!(stats[1].average_delay, None);
+
+        let out = format_edges(&edges);
+        assert!(out.contains("Frame: base_link, parent: world, published by a, Average Delay: 0.010, Max Delay: 0.020, Average Rate: 10.000 Hz\n"));
+        assert!(out.contains("Frame: camera, parent: base_link, published by a, static\n"));
+        assert!(out.contains("Node: a, 40.000 Hz, Average Delay: 0.025, Max Delay: 0.050\n"));
+        assert!(out.contains("Node: b, 5.000 Hz, Average Delay: unknown, Max Delay: unknown\n"));
+    }
+
+    #[test]
+    fn test_format_chain() {
+        let edges = vec![
+            edge("world", "base_link", "a", false, 10, Some((0.01, 0.02))),
+            edge("base_link", "arm", "a", false, 30, Some((0.03, 0.05))),
+            edge("world", "target", "b", false, 5, Some((0.1, 0.2)))
+        ];
+        let hops = vec![hop("base_link", "arm", true), hop("world", "base_link", true), hop("world", "target", false)];
+        assert_eq!(format_chain("arm", &hops, &edges), "Chain is: arm -> base_link -> world -> target\nNet delay     avg = 0.140: max = 0.270\n");
+    }
+}
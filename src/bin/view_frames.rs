@@ -0,0 +1,109 @@
+//! Writes the frame tree to a Graphviz DOT file, like `view_frames` from `tf2_tools`.
+//!
+//! ```text
+//! view_frames [--duration seconds] [--save snapshot_file] [--output frames.gv]
+//! view_frames --load snapshot_file [--output frames.gv]
+//! ```
+//!
+//! By default the transforms published over the next 5 seconds are collected. Instead, a snapshot saved by an
+//! earlier run with `--save` or by `TfBuffer::save_snapshot` can be loaded. Render the output with
+//! `dot -Tpdf frames.gv -o frames.pdf`.
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+
+use rustros_tf::{TfBuffer, TfListener};
+
+mod common;
+
+const USAGE: &str = "Usage:
+  view_frames [--duration seconds] [--save snapshot_file] [--output frames.gv]
+  view_frames --load snapshot_file [--output frames.gv]";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    duration: f64,
+    load: Option<String>,
+    save: Option<String>,
+    output: String
+}
+
+fn main() {
+    let args = common::command_line_args();
+    let options = parse_args(&args).unwrap_or_else(|e| fail(&format!("{}\n{}", e, USAGE)));
+
+    let dot = match options.load {
+        Some(ref path) => {
+            let file = File::open(path).unwrap_or_else(|e| fail(&format!("Could not open {}: {}", path, e)));
+            let buffer = TfBuffer::load_snapshot(BufReader::new(file))
+                .unwrap_or_else(|e| fail(&format!("Could not load {}: {}", path, e)));
+            buffer.all_frames_as_dot()
+        },
+        None => {
+            rosrust::init(&format!("view_frames_{}", process::id()));
+            let listener = TfListener::new();
+            println!("Listening to /tf for {} seconds", options.duration);
+            std::thread::sleep(std::time::Duration::from_secs_f64(options.duration));
+            if let Some(ref path) = options.save {
+                let file = File::create(path).unwrap_or_else(|e| fail(&format!("Could not create {}: {}", path, e)));
+                listener.save_snapshot(BufWriter::new(file))
+                    .unwrap_or_else(|e| fail(&format!("Could not save {}: {}", path, e)));
+                println!("Saved a snapshot to {}", path);
+            }
+            listener.all_frames_as_dot()
+        }
+    };
+
+    std::fs::write(&options.output, dot).unwrap_or_else(|e| fail(&format!("Could not write {}: {}", options.output, e)));
+    println!("Wrote {}, render it with: dot -Tpdf {} -o frames.pdf", options.output, options.output);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Parses the command line, without the program name.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options{duration: 5f64, load: None, save: None, output: "frames.gv".to_string()};
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--duration" => {
+                let value = value()?;
+                options.duration = value.parse().map_err(|_| format!("{} is not a number", value))?;
+                if !options.duration.is_finite() || options.duration < 0f64 {
+                    return Err(format!("The duration must not be negative, but is {}", value));
+                }
+            },
+            "--load" => options.load = Some(value()?),
+            "--save" => options.save = Some(value()?),
+            "--output" => options.output = value()?,
+            _ => return Err(format!("Unknown argument {}", arg))
+        }
+    }
+    if options.load.is_some() && options.save.is_some() {
+        return Err("--load and --save can not be combined".to_string());
+    }
+    Ok(options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::args;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&args("")).unwrap(), Options{duration: 5f64, load: None, save: None, output: "frames.gv".to_string()});
+        let options = parse_args(&args("--duration 2.5 --save tf.snapshot --output tree.gv")).unwrap();
+        assert_eq!(options, Options{duration: 2.5f64, load: None, save: Some("tf.snapshot".to_string()), output: "tree.gv".to_string()});
+        assert_eq!(parse_args(&args("--load tf.snapshot")).unwrap().load, Some("tf.snapshot".to_string()));
+
+        assert!(parse_args(&args("--load a --save b")).is_err());
+        assert!(parse_args(&args("--duration")).is_err());
+        assert!(parse_args(&args("--duration -1")).is_err());
+        assert!(parse_args(&args("frames.gv")).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use rosrust::{Message, RosMsg};
+
+use crate::msg;
+use crate::tf_buffer::TfBuffer;
+use crate::tf_error::TfError;
+
+/// Every rosbag v1 file starts with this line.
+const BAG_MAGIC: &[u8] = b"#ROSBAG V2.0\n";
+
+/// The bag header record is padded to this length so that it can be rewritten in place once the bag is complete.
+const BAG_HEADER_LENGTH: usize = 4096;
+
+/// Chunks are written once they hold this many bytes, the same as `rosbag record`.
+const CHUNK_THRESHOLD: usize = 768 * 1024;
+
+const OP_MESSAGE_DATA: u8 = 0x02;
+const OP_BAG_HEADER: u8 = 0x03;
+const OP_INDEX_DATA: u8 = 0x04;
+const OP_CHUNK: u8 = 0x05;
+const OP_CHUNK_INFO: u8 = 0x06;
+const OP_CONNECTION: u8 = 0x07;
+
+/// Fields of a record header, by name.
+type RecordHeader = HashMap<String, Vec<u8>>;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Fills `buffer` with the transforms recorded on `/tf` and `/tf_static` in a rosbag v1 file. Other topics are
+/// skipped. The time each message was recorded is used as its receive time and the caller id of the recorded
+/// connection as its authority.
+///
+/// Only uncompressed bags can be read. As the buffer discards transforms older than its cache duration, use
+/// `TfBuffer::new_with_duration` to keep the whole bag. Returns the errors for the transforms which were rejected.
+///
+/// Example usage:
+///
+/// ```ignore
+/// let mut buffer = TfBuffer::new_with_duration(rosrust::Duration::from_seconds(3600));
+/// read_bag(BufReader::new(File::open("robot.bag")?), &mut buffer)?;
+/// ```
+pub fn read_bag<R: Read>(mut reader: R, buffer: &mut TfBuffer) -> io::Result<Vec<TfError>> {
+    let mut magic = [0u8; 13];
+    reader.read_exact(&mut magic)?;
+    if magic != BAG_MAGIC {
+        return Err(invalid_data("Not a rosbag v2.0 file".to_string()));
+    }
+    let mut bag = BagReader{connections: HashMap::new(), errors: Vec::new()};
+    while let Some((header, data)) = read_record(&mut reader)? {
+        bag.handle_record(&header, data, buffer)?;
+    }
+    Ok(bag.errors)
+}
+
+/// A connection recorded in a bag which carries transforms.
+struct TfConnection {
+    static_tf: bool,
+    authority: String
+}
+
+struct BagReader {
+    /// Connections on `/tf` and `/tf_static`, by id
+    connections: HashMap<u32, TfConnection>,
+    errors: Vec<TfError>
+}
+
+impl BagReader {
+    fn handle_record(&mut self, header: &RecordHeader, data: Vec<u8>, buffer: &mut TfBuffer) -> io::Result<()> {
+        match header_field(header, "op")?.first() {
+            Some(&OP_CHUNK) => {
+                let compression = String::from_utf8_lossy(header_field(header, "compression")?).to_string();
+                if compression != "none" {
+                    return Err(invalid_data(format!("Chunks compressed with {} are not supported", compression)));
+                }
+                let mut chunk = io::Cursor::new(data);
+                while let Some((header, data)) = read_record(&mut chunk)? {
+                    self.handle_record(&header, data, buffer)?;
+                }
+            },
+            Some(&OP_CONNECTION) => {
+                let id = header_u32(header, "conn")?;
+                let mut connection_header = io::Cursor::new(data);
+                let fields = read_header_fields(&mut connection_header)?;
+                let topic = String::from_utf8_lossy(header_field(&fields, "topic")?).to_string();
+                let msg_type = String::from_utf8_lossy(header_field(&fields, "type")?).to_string();
+                let static_tf = match topic.trim_start_matches('/') {
+                    "tf" => false,
+                    "tf_static" => true,
+                    _ => return Ok(())
+                };
+                // tf/tfMessage has the same layout as tf2_msgs/TFMessage
+                if msg_type != msg::tf2_msgs::TFMessage::msg_type() && msg_type != "tf/tfMessage" {
+                    return Ok(());
+                }
+                let authority = fields.get("callerid").map(|x| String::from_utf8_lossy(x).to_string()).unwrap_or_default();
+                self.connections.insert(id, TfConnection{static_tf, authority});
+            },
+            Some(&OP_MESSAGE_DATA) => {
+                let id = header_u32(header, "conn")?;
+                if let Some(connection) = self.connections.get(&id) {
+                    let time = rosrust::Time::decode(header_field(header, "time")?)?;
+                    let msg = msg::tf2_msgs::TFMessage::decode(data.as_slice())?;
+                    let errors = buffer.handle_incoming_transforms(msg, connection.static_tf, &connection.authority, time);
+                    self.errors.extend(errors);
+                }
+            },
+            // The remaining records only index the bag, which is not needed when reading all of it.
+            Some(&OP_BAG_HEADER) | Some(&OP_INDEX_DATA) | Some(&OP_CHUNK_INFO) => {},
+            _ => return Err(invalid_data("Unknown record type".to_string()))
+        }
+        Ok(())
+    }
+}
+
+/// Reads a record, returning `None` at the end of the input.
+fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<(RecordHeader, Vec<u8>)>> {
+    let mut length = [0u8; 4];
+    let mut read = 0;
+    while read < length.len() {
+        match reader.read(&mut length[read..])? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated record")),
+            n => read += n
+        }
+    }
+    let header = read_bytes(reader, u32::from_le_bytes(length))?;
+    let header = read_header_fields(&mut io::Cursor::new(header))?;
+    let length = u32::decode(&mut *reader)?;
+    let data = read_bytes(reader, length)?;
+    Ok(Some((header, data)))
+}
+
+/// Reads `length` bytes. As the length comes from the file, the bytes are only allocated as they are read so that
+/// a corrupt length fails with an error instead of allocating up to 4 GiB.
+fn read_bytes<R: Read>(reader: &mut R, length: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(u64::from(length)).read_to_end(&mut bytes)?;
+    if bytes.len() != length as usize {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated record"));
+    }
+    Ok(bytes)
+}
+
+/// Reads `name=value` fields, each prefixed by its length, until the end of the input.
+fn read_header_fields(reader: &mut io::Cursor<Vec<u8>>) -> io::Result<RecordHeader> {
+    let mut fields = HashMap::new();
+    while (reader.position() as usize) < reader.get_ref().len() {
+        let length = u32::decode(&mut *reader)?;
+        let mut field = read_bytes(reader, length)?;
+        let separator = field.iter().position(|x| *x == b'=')
+            .ok_or_else(|| invalid_data("Record header field without a name".to_string()))?;
+        let value = field.split_off(separator + 1);
+        field.pop();
+        fields.insert(String::from_utf8_lossy(&field).to_string(), value);
+    }
+    Ok(fields)
+}
+
+fn header_field<'a>(header: &'a RecordHeader, name: &str) -> io::Result<&'a [u8]> {
+    header.get(name)
+        .map(|x| x.as_slice())
+        .ok_or_else(|| invalid_data(format!("Record header has no {} field", name)))
+}
+
+fn header_u32(header: &RecordHeader, name: &str) -> io::Result<u32> {
+    u32::decode(header_field(header, name)?)
+}
+
+/// Encodes `name=value` fields, each prefixed by its length.
+fn encode_header_fields(fields: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut res = Vec::new();
+    for (name, value) in fields {
+        res.extend_from_slice(&((name.len() + 1 + value.len()) as u32).to_le_bytes());
+        res.extend_from_slice(name.as_bytes());
+        res.push(b'=');
+        res.extend_from_slice(value);
+    }
+    res
+}
+
+fn write_record<W: Write>(writer: &mut W, fields: &[(&str, Vec<u8>)], data: &[u8]) -> io::Result<()> {
+    let header = encode_header_fields(fields);
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(&header)?;
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(data)
+}
+
+fn encode_time(time: rosrust::Time) -> Vec<u8> {
+    let mut res = time.sec.to_le_bytes().to_vec();
+    res.extend_from_slice(&time.nsec.to_le_bytes());
+    res
+}
+
+/// A connection written to a bag.
+struct BagConnection {
+    topic: String,
+    authority: String,
+    static_tf: bool
+}
+
+/// Where the messages of a chunk were written, for the index at the end of the bag.
+struct ChunkInfo {
+    position: u64,
+    start: rosrust::Time,
+    end: rosrust::Time,
+    /// Number of messages in the chunk, by connection id
+    counts: BTreeMap<u32, u32>
+}
+
+/// Writes transforms to a rosbag v1 file which can be played back with `rosbag play` or read with `read_bag`.
+/// Transforms are written to `/tf` or `/tf_static`, with one connection for every node publishing them.
+///
+/// The bag is completed when the writer is dropped, or earlier by calling `finish`. Use
+/// `TfListener::start_recording` to record the transforms received by a listener.
+pub struct BagWriter<W: Write + Seek> {
+    writer: W,
+    /// Connections by id
+    connections: Vec<BagConnection>,
+    /// Records of the chunk which is being filled
+    chunk: Vec<u8>,
+    /// Time and offset within the chunk of every message in it, by connection id
+    chunk_index: BTreeMap<u32, Vec<(rosrust::Time, u32)>>,
+    chunk_infos: Vec<ChunkInfo>,
+    finished: bool
+}
+
+impl BagWriter<BufWriter<File>> {
+    /// Creates a bag file at `path`, replacing any existing file.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        BagWriter::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Seek> BagWriter<W> {
+    /// Starts a bag at the current position of `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(BAG_MAGIC)?;
+        let mut bag = BagWriter{writer, connections: Vec::new(), chunk: Vec::new(), chunk_index: BTreeMap::new(),
+            chunk_infos: Vec::new(), finished: false};
+        // The index is not known yet, so write a placeholder to be replaced by `finish`
+        bag.write_bag_header(0)?;
+        Ok(bag)
+    }
+
+    /// Writes a message received at `time` from the node `authority`. Fails once the bag has been finished.
+    pub fn write(&mut self, transforms: &msg::tf2_msgs::TFMessage, static_tf: bool, authority: &str, time: rosrust::Time) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::other("The bag has already been finished"));
+        }
+        let id = self.connection_id(static_tf, authority)?;
+        let offset = self.chunk.len() as u32;
+        let mut data = Vec::new();
+        transforms.encode(&mut data)?;
+        write_record(&mut self.chunk, &[
+            ("op", vec![OP_MESSAGE_DATA]),
+            ("conn", id.to_le_bytes().to_vec()),
+            ("time", encode_time(time))
+        ], &data)?;
+        self.chunk_index.entry(id).or_default().push((time, offset));
+        if self.chunk.len() >= CHUNK_THRESHOLD {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining messages and the index of the bag. Nothing can be written afterwards.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.write_chunk()?;
+        let index_position = self.writer.stream_position()?;
+        for id in 0..self.connections.len() {
+            let record = self.connection_record(id as u32)?;
+            self.writer.write_all(&record)?;
+        }
+        for info in &self.chunk_infos {
+            let mut data = Vec::new();
+            for (id, count) in &info.counts {
+                data.extend_from_slice(&id.to_le_bytes());
+                data.extend_from_slice(&count.to_le_bytes());
+            }
+            write_record(&mut self.writer, &[
+                ("op", vec![OP_CHUNK_INFO]),
+                ("ver", 1u32.to_le_bytes().to_vec()),
+                ("chunk_pos", info.position.to_le_bytes().to_vec()),
+                ("start_time", encode_time(info.start)),
+                ("end_time", encode_time(info.end)),
+                ("count", (info.counts.len() as u32).to_le_bytes().to_vec())
+            ], &data)?;
+        }
+        self.writer.seek(SeekFrom::Start(BAG_MAGIC.len() as u64))?;
+        self.write_bag_header(index_position)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+
+    fn write_bag_header(&mut self, index_position: u64) -> io::Result<()> {
+        let fields = [
+            ("op", vec![OP_BAG_HEADER]),
+            ("index_pos", index_position.to_le_bytes().to_vec()),
+            ("conn_count", (self.connections.len() as u32).to_le_bytes().to_vec()),
+            ("chunk_count", (self.chunk_infos.len() as u32).to_le_bytes().to_vec())
+        ];
+        let padding = BAG_HEADER_LENGTH - 8 - encode_header_fields(&fields).len();
+        write_record(&mut self.writer, &fields, &vec![b' '; padding])
+    }
+
+    /// Finds the connection for the topic and node, adding it to the current chunk if it is new.
+    fn connection_id(&mut self, static_tf: bool, authority: &str) -> io::Result<u32> {
+        let existing = self.connections.iter().position(|x| x.static_tf == static_tf && x.authority == authority);
+        if let Some(id) = existing {
+            return Ok(id as u32);
+        }
+        let topic = if static_tf { "/tf_static" } else { "/tf" };
+        self.connections.push(BagConnection{topic: topic.to_string(), authority: authority.to_string(), static_tf});
+        let id = (self.connections.len() - 1) as u32;
+        let record = self.connection_record(id)?;
+        self.chunk.extend_from_slice(&record);
+        Ok(id)
+    }
+
+    fn connection_record(&self, id: u32) -> io::Result<Vec<u8>> {
+        let connection = &self.connections[id as usize];
+        let data = encode_header_fields(&[
+            ("topic", connection.topic.as_bytes().to_vec()),
+            ("type", msg::tf2_msgs::TFMessage::msg_type().into_bytes()),
+            ("md5sum", msg::tf2_msgs::TFMessage::md5sum().into_bytes()),
+            ("message_definition", msg::tf2_msgs::TFMessage::msg_definition().into_bytes()),
+            ("callerid", connection.authority.as_bytes().to_vec()),
+            ("latching", if connection.static_tf { b"1".to_vec() } else { b"0".to_vec() })
+        ]);
+        let mut record = Vec::new();
+        write_record(&mut record, &[
+            ("op", vec![OP_CONNECTION]),
+            ("conn", id.to_le_bytes().to_vec()),
+            ("topic", connection.topic.as_bytes().to_vec())
+        ], &data)?;
+        Ok(record)
+    }
+
+    /// Writes the current chunk followed by its index.
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let position = self.writer.stream_position()?;
+        write_record(&mut self.writer, &[
+            ("op", vec![OP_CHUNK]),
+            ("compression", b"none".to_vec()),
+            ("size", (self.chunk.len() as u32).to_le_bytes().to_vec())
+        ], &self.chunk)?;
+
+        let mut info = ChunkInfo{position, start: rosrust::Time{sec: u32::MAX, nsec: 0}, end: rosrust::Time::new(), counts: BTreeMap::new()};
+        for (id, entries) in &self.chunk_index {
+            let mut data = Vec::new();
+            for (time, offset) in entries {
+                data.extend_from_slice(&encode_time(*time));
+                data.extend_from_slice(&offset.to_le_bytes());
+                info.start = info.start.min(*time);
+                info.end = info.end.max(*time);
+            }
+            write_record(&mut self.writer, &[
+                ("op", vec![OP_INDEX_DATA]),
+                ("ver", 1u32.to_le_bytes().to_vec()),
+                ("conn", id.to_le_bytes().to_vec()),
+                ("count", (entries.len() as u32).to_le_bytes().to_vec())
+            ], &data)?;
+            info.counts.insert(*id, entries.len() as u32);
+        }
+        self.chunk_infos.push(info);
+        self.chunk.clear();
+        self.chunk_index.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for BagWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_transform;
+
+    fn write_test_bag(messages: usize) -> Vec<u8> {
+        let mut data = io::Cursor::new(Vec::new());
+        {
+            let mut bag = BagWriter::new(&mut data).unwrap();
+            let statics = msg::tf2_msgs::TFMessage{transforms: vec![test_transform("base_link", "camera", 0f64, [0.5f64, 0f64, 0f64])]};
+            bag.write(&statics, true, "/static_publisher", rosrust::Time{sec: 0, nsec: 100}).unwrap();
+            for i in 0..messages {
+                let msg = msg::tf2_msgs::TFMessage{transforms: vec![test_transform("world", "base_link", i as f64, [i as f64, 0f64, 0f64])]};
+                bag.write(&msg, false, "/odometry", rosrust::Time{sec: i as u32, nsec: 1000}).unwrap();
+            }
+        }
+        data.into_inner()
+    }
+
+    /// Tests that transforms written to a bag are read back into a buffer.
+    #[test]
+    fn test_write_and_read_bag() {
+        let data = write_test_bag(3);
+        assert_eq!(&data[..BAG_MAGIC.len()], BAG_MAGIC);
+
+        let mut buffer = TfBuffer::new();
+        let errors = read_bag(data.as_slice(), &mut buffer).unwrap();
+        assert!(errors.is_empty());
+        let tf = buffer.lookup_transform("world", "camera", rosrust::Time{sec: 1, nsec: 500_000_000}).unwrap();
+        assert!((tf.transform.translation.x - 2f64).abs() < 1e-9);
+
+        let edges = buffer.edges();
+        assert_eq!(edges[0].authority, "/static_publisher");
+        assert!(edges[0].static_tf);
+        assert_eq!(edges[1].authority, "/odometry");
+        assert_eq!(edges[1].samples, 3);
+        assert_eq!(edges[1].max_delay, Some(rosrust::Duration{sec: 0, nsec: 1000}));
+    }
+
+    /// Tests the index written at the end of a bag spanning several chunks.
+    #[test]
+    fn test_bag_index() {
+        let messages = CHUNK_THRESHOLD / 100 + 1;
+        let data = write_test_bag(messages);
+        let mut reader = io::Cursor::new(&data[BAG_MAGIC.len()..]);
+        let (header, padding) = read_record(&mut reader).unwrap().unwrap();
+        assert_eq!(reader.position() as usize, BAG_HEADER_LENGTH);
+        assert!(padding.iter().all(|x| *x == b' '));
+        assert_eq!(header_u32(&header, "conn_count").unwrap(), 2);
+        let chunk_count = header_u32(&header, "chunk_count").unwrap();
+        assert!(chunk_count >= 2);
+
+        let index_position = u64::decode(header_field(&header, "index_pos").unwrap()).unwrap() as usize;
+        let mut reader = io::Cursor::new(&data[index_position..]);
+        let mut connections = 0;
+        let mut counts = 0;
+        while let Some((header, data)) = read_record(&mut reader).unwrap() {
+            match header_field(&header, "op").unwrap()[0] {
+                OP_CONNECTION => connections += 1,
+                OP_CHUNK_INFO => {
+                    let mut data = data.as_slice();
+                    for _ in 0..header_u32(&header, "count").unwrap() {
+                        u32::decode(&mut data).unwrap();
+                        counts += u32::decode(&mut data).unwrap() as usize;
+                    }
+                },
+                op => panic!("Unexpected record {} in the index", op)
+            }
+        }
+        assert_eq!(connections, 2);
+        assert_eq!(counts, messages + 1);
+    }
+
+    /// Tests that other topics are skipped and that invalid bags are rejected.
+    #[test]
+    fn test_read_bag_errors() {
+        let mut data = io::Cursor::new(Vec::new());
+        data.write_all(BAG_MAGIC).unwrap();
+        let connection = encode_header_fields(&[("topic", b"/odom".to_vec()), ("type", b"nav_msgs/Odometry".to_vec())]);
+        write_record(&mut data, &[("op", vec![OP_CONNECTION]), ("conn", 0u32.to_le_bytes().to_vec()), ("topic", b"/odom".to_vec())], &connection).unwrap();
+        write_record(&mut data, &[("op", vec![OP_MESSAGE_DATA]), ("conn", 0u32.to_le_bytes().to_vec()), ("time", encode_time(rosrust::Time::new()))], b"odom").unwrap();
+        let mut buffer = TfBuffer::new();
+        assert!(read_bag(data.get_ref().as_slice(), &mut buffer).unwrap().is_empty());
+        assert!(buffer.all_frames().is_empty());
+
+        write_record(&mut data, &[("op", vec![OP_CHUNK]), ("compression", b"bz2".to_vec()), ("size", 0u32.to_le_bytes().to_vec())], &[]).unwrap();
+        assert!(read_bag(data.get_ref().as_slice(), &mut buffer).is_err());
+
+        let data = write_test_bag(1);
+        assert!(read_bag(&data[..data.len() - 1], &mut buffer).is_err());
+        assert!(read_bag(&b"#ROSBAG V1.2\n"[..], &mut buffer).is_err());
+
+        // A corrupt length must fail without trying to allocate it
+        let mut data = BAG_MAGIC.to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(read_bag(data.as_slice(), &mut buffer).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    /// Tests that nothing can be written to a finished bag.
+    #[test]
+    fn test_write_after_finish() {
+        let mut data = io::Cursor::new(Vec::new());
+        let mut bag = BagWriter::new(&mut data).unwrap();
+        bag.finish().unwrap();
+        let msg = msg::tf2_msgs::TFMessage{transforms: vec![test_transform("world", "base_link", 0f64, [0f64; 3])]};
+        assert!(bag.write(&msg, false, "/odometry", rosrust::Time::new()).is_err());
+        drop(bag);
+        assert_eq!(data.get_ref().len(), BAG_MAGIC.len() + BAG_HEADER_LENGTH);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{msg, to_transform_stamped, transforms};
+
+/// Publishes transforms on `/tf` so that they can be picked up by any `TfListener`, like the C++
+/// `TransformBroadcaster`.
+///
+/// Example usage:
+///
+/// ```ignore
+/// fn main() {
+///     rosrust::init("broadcaster");
+///     let mut broadcaster = TfBroadcaster::new();
+///
+///     let rate = rosrust::rate(10.0);
+///     while rosrust::is_ok() {
+///         let tf = transforms::Transform{
+///             orientation: transforms::Quaternion{x: 0f64, y: 0f64, z: 0f64, w: 1f64},
+///             position: transforms::Position{x: 1f64, y: 0f64, z: 0f64}
+///         };
+///         broadcaster.send_transform_with_frames(&tf, "world", "base_link", rosrust::now()).unwrap();
+///         rate.sleep();
+///     }
+/// }
+/// ```
+pub struct TfBroadcaster {
+    publisher: rosrust::Publisher<msg::tf2_msgs::TFMessage>,
+    next_seq: u32
+}
+
+impl TfBroadcaster {
+
+    /// Create a new TfBroadcaster publishing on `/tf`.
+    pub fn new() -> Self {
+        let publisher = rosrust::publish("tf", 100).unwrap();
+        TfBroadcaster{publisher, next_seq: 0}
+    }
+
+    /// Publishes a single transform.
+    pub fn send_transform(&mut self, transform: msg::geometry_msgs::TransformStamped) -> Result<(), rosrust::error::Error> {
+        self.send_transforms(vec![transform])
+    }
+
+    /// Publishes several transforms in a single message. The sequence numbers of their headers are filled in.
+    pub fn send_transforms(&mut self, transforms: Vec<msg::geometry_msgs::TransformStamped>) -> Result<(), rosrust::error::Error> {
+        let msg = sequenced_message(&mut self.next_seq, transforms);
+        self.publisher.send(msg)
+    }
+
+    /// Publishes the transform from `parent` to `child` at `time`.
+    pub fn send_transform_with_frames(&mut self, transform: &transforms::Transform, parent: &str, child: &str, time: rosrust::Time) -> Result<(), rosrust::error::Error> {
+        self.send_transform(to_transform_stamped(*transform, parent.to_string(), child.to_string(), time))
+    }
+}
+
+impl Default for TfBroadcaster {
+    fn default() -> Self {
+        TfBroadcaster::new()
+    }
+}
+
+/// Wraps the transforms into a message, numbering their headers consecutively starting from `next_seq`.
+pub(crate) fn sequenced_message(next_seq: &mut u32, mut transforms: Vec<msg::geometry_msgs::TransformStamped>) -> msg::tf2_msgs::TFMessage {
+    for transform in transforms.iter_mut() {
+        transform.header.seq = *next_seq;
+        *next_seq = next_seq.wrapping_add(1);
+    }
+    msg::tf2_msgs::TFMessage{transforms}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_transform;
+
+    /// Tests that sequence numbers keep counting up across messages.
+    #[test]
+    fn test_sequenced_message() {
+        let mut next_seq = 0;
+        let msg = sequenced_message(&mut next_seq, vec![test_transform("world", "a", 0f64, [0f64; 3]), test_transform("world", "b", 0f64, [0f64; 3])]);
+        assert_eq!(msg.transforms.iter().map(|x| x.header.seq).collect::<Vec<_>>(), vec![0, 1]);
+
+        let msg = sequenced_message(&mut next_seq, vec![test_transform("a", "c", 0f64, [0f64; 3])]);
+        assert_eq!(msg.transforms[0].header.seq, 2);
+        assert_eq!(next_seq, 3);
+    }
+}
@@ -0,0 +1,1204 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+
+use rosrust::RosMsg;
+
+use crate::{msg, to_transform, to_transform_stamped, transforms};
+use crate::tf_error::TfError;
+use crate::tf_extrapolation::{ExtrapolatedTransform, ExtrapolationPolicy};
+use crate::tf_interpolation::InterpolationMode;
+use crate::tf_provenance::{DetailedTransform, TransformHop};
+use crate::tf_edge_info::TfEdgeInfo;
+use crate::tf_graph_node::TfGraphNode;
+use crate::tf_individual_transform_chain::{EdgeLookupOptions, TfIndividualTransformChain};
+use crate::tf_future::TfWakers;
+
+/// A cache of transforms between coordinate frames. 
+///
+/// `TfBuffer` does not depend on a running ROS master, so it can be filled from any source such as a log file, 
+/// a simulation or a unit test using `set_transform`. `TfListener` uses a `TfBuffer` internally to store the 
+/// transforms it receives on `/tf` and `/tf_static`.
+///
+/// Example usage:
+///
+/// ```ignore
+/// let mut buffer = TfBuffer::new();
+/// buffer.set_transform(&world_to_base_link, "simulator", false);
+/// let tf = buffer.lookup_transform("world", "base_link", rosrust::Time::from_seconds(1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TfBuffer {
+    child_transform_index: HashMap<String, HashSet<String> >,
+    parent_transform_index: HashMap<String, String>,
+    transform_data: HashMap<TfGraphNode, TfIndividualTransformChain>,
+    cache_duration: rosrust::Duration,
+    extrapolation_policy: ExtrapolationPolicy,
+    /// Interpolation modes of the edges which do not use the default
+    interpolation_modes: HashMap<TfGraphNode, InterpolationMode>,
+    /// Largest time between two samples which may be interpolated across, unless overridden for an edge
+    max_interpolation_gap: Option<rosrust::Duration>,
+    max_interpolation_gap_overrides: HashMap<TfGraphNode, Option<rosrust::Duration>>,
+    /// Tasks waiting for new transforms to be added
+    wakers: TfWakers
+}
+
+/// The default amount of history kept for each edge, the same as tf2.
+pub const DEFAULT_CACHE_DURATION_SECONDS: i32 = 10;
+
+/// Identifies the files written by `save_snapshot`.
+const SNAPSHOT_HEADER: &str = "rustros_tf snapshot 1";
+
+
+impl TfBuffer {
+
+    /// Create a new, empty TfBuffer which keeps `DEFAULT_CACHE_DURATION_SECONDS` of history for every edge.
+    pub fn new() -> Self {
+        TfBuffer::new_with_duration(rosrust::Duration{sec: DEFAULT_CACHE_DURATION_SECONDS, nsec: 0})
+    }
+
+    /// Create a new, empty TfBuffer. For every edge, transforms older than `cache_duration` relative to the 
+    /// latest transform on that edge are discarded.
+    pub fn new_with_duration(cache_duration: rosrust::Duration) -> Self {
+        TfBuffer{child_transform_index: HashMap::new(), parent_transform_index: HashMap::new(), transform_data: HashMap::new(), cache_duration, extrapolation_policy: ExtrapolationPolicy::Strict, 
+            interpolation_modes: HashMap::new(), max_interpolation_gap: None, max_interpolation_gap_overrides: HashMap::new(), 
+            wakers: TfWakers::default()}
+    }
+
+    /// Returns how much history is kept for every edge.
+    pub fn cache_duration(&self) -> rosrust::Duration {
+        self.cache_duration
+    }
+
+    /// Sets the policy used by `lookup_transform` and `can_transform` for lookups outside of the range of data
+    /// available on an edge. By default lookups outside of the available data fail.
+    pub fn set_extrapolation_policy(&mut self, policy: ExtrapolationPolicy) {
+        self.extrapolation_policy = policy;
+    }
+
+    /// Returns the policy used for lookups outside of the range of data available on an edge.
+    pub fn extrapolation_policy(&self) -> ExtrapolationPolicy {
+        self.extrapolation_policy
+    }
+
+    /// Sets how transforms on the edge from `parent` to `child` are interpolated between samples. The edge does not 
+    /// need to exist yet. Edges use `InterpolationMode::Linear` unless set otherwise.
+    pub fn set_interpolation_mode(&mut self, parent: &str, child: &str, mode: InterpolationMode) {
+        self.interpolation_modes.insert(TfGraphNode{child: child.to_string(), parent: parent.to_string()}, mode);
+    }
+
+    /// Returns how transforms on the edge from `parent` to `child` are interpolated between samples.
+    pub fn interpolation_mode(&self, parent: &str, child: &str) -> InterpolationMode {
+        // Few buffers override any edge, so avoid building a key for every edge of every lookup
+        if self.interpolation_modes.is_empty() {
+            return InterpolationMode::default();
+        }
+        let node = TfGraphNode{child: child.to_string(), parent: parent.to_string()};
+        self.interpolation_modes.get(&node).copied().unwrap_or_default()
+    }
+
+    /// Sets the largest time between two samples that lookups may interpolate across. Lookups falling in a larger gap 
+    /// fail with `TfError::InterpolationGapExceeded`. `None`, the default, allows gaps of any size.
+    pub fn set_max_interpolation_gap(&mut self, max_gap: Option<rosrust::Duration>) {
+        self.max_interpolation_gap = max_gap;
+    }
+
+    /// Overrides the largest time between two samples that lookups may interpolate across for the edge from `parent` 
+    /// to `child`. The edge does not need to exist yet.
+    pub fn set_edge_max_interpolation_gap(&mut self, parent: &str, child: &str, max_gap: Option<rosrust::Duration>) {
+        self.max_interpolation_gap_overrides.insert(TfGraphNode{child: child.to_string(), parent: parent.to_string()}, max_gap);
+    }
+
+    /// Returns the largest time between two samples that lookups may interpolate across on the edge from `parent` to `child`.
+    pub fn max_interpolation_gap(&self, parent: &str, child: &str) -> Option<rosrust::Duration> {
+        // Without any overrides the buffer wide gap applies and no key needs to be built
+        if self.max_interpolation_gap_overrides.is_empty() {
+            return self.max_interpolation_gap;
+        }
+        let node = TfGraphNode{child: child.to_string(), parent: parent.to_string()};
+        match self.max_interpolation_gap_overrides.get(&node) {
+            Some(max_gap) => *max_gap,
+            None => self.max_interpolation_gap
+        }
+    }
+
+    /// Adds all the transforms in a message received at `receive_time`, returning the errors for the transforms 
+    /// which were rejected.
+    pub(crate) fn handle_incoming_transforms(&mut self, transforms: msg::tf2_msgs::TFMessage, static_tf: bool, authority: &str, 
+        receive_time: rosrust::Time) -> Vec<TfError> {
+        let mut errors = vec!();
+        for transform in transforms.transforms {
+            if let Err(e) = self.add_transform(&transform, authority, static_tf, Some(receive_time)) {
+                errors.push(e);
+            }
+        }
+        self.wakers.wake_all();
+        errors
+    }
+
+    /// Adds a transform to the buffer. `authority` identifies the source of the transform (for a `TfListener` 
+    /// this is the caller id of the publishing node). Static transforms are valid for all times.
+    ///
+    /// Transforms which would give a frame a second parent or create a loop in the tree are rejected.
+    pub fn set_transform(&mut self, transform: &msg::geometry_msgs::TransformStamped, authority: &str, is_static: bool) -> Result<(), TfError> {
+        let res = self.add_transform(transform, authority, is_static, None);
+        self.wakers.wake_all();
+        res
+    }
+
+    /// Tasks which are woken the next time transforms are added to the buffer.
+    pub(crate) fn wakers(&self) -> &TfWakers {
+        &self.wakers
+    }
+
+    /// Removes all transforms from the buffer.
+    pub fn clear(&mut self) {
+        self.child_transform_index.clear();
+        self.parent_transform_index.clear();
+        self.transform_data.clear();
+    }
+
+    fn add_transform (&mut self, transform: &msg::geometry_msgs::TransformStamped, authority: &str, static_tf: bool, 
+        receive_time: Option<rosrust::Time>) -> Result<(), TfError> {
+        self.check_new_edge(&transform.header.frame_id, &transform.child_frame_id, authority)?;
+        if !self.parent_transform_index.contains_key(&transform.child_frame_id) {
+            self.parent_transform_index.insert(transform.child_frame_id.clone(), transform.header.frame_id.clone());
+        }
+
+        if self.child_transform_index.contains_key(&transform.header.frame_id) {
+            let res = self.child_transform_index.get_mut(&transform.header.frame_id.clone()).unwrap();
+            res.insert(transform.child_frame_id.clone());
+        }
+        else {
+            self.child_transform_index.insert(transform.header.frame_id.clone(), HashSet::new());
+            let res = self.child_transform_index.get_mut(&transform.header.frame_id.clone()).unwrap();
+            res.insert(transform.child_frame_id.clone());
+        }
+        
+        let key = TfGraphNode{child: transform.child_frame_id.clone(), parent: transform.header.frame_id.clone()};
+        
+        if self.transform_data.contains_key(&key) {
+            let data = self.transform_data.get_mut(&key).unwrap();
+            data.add_to_buffer(transform.clone(), authority, receive_time);
+        }
+        else {
+            let mut data = TfIndividualTransformChain::new(static_tf, self.cache_duration);
+            data.add_to_buffer(transform.clone(), authority, receive_time);
+            self.transform_data.insert(key, data);
+        }
+        Ok(())
+    }
+
+    /// Checks that adding an edge from `parent` to `child` keeps the graph a tree.
+    fn check_new_edge(&self, parent: &str, child: &str, authority: &str) -> Result<(), TfError> {
+        if let Some(existing_parent) = self.parent_transform_index.get(child) {
+            if existing_parent == parent {
+                return Ok(());
+            }
+            let existing_authority = self.transform_data
+                .get(&TfGraphNode{child: child.to_string(), parent: existing_parent.clone()})
+                .map(|data| data.authority().to_string())
+                .unwrap_or_default();
+            return Err(TfError::MultipleParents{
+                child: child.to_string(),
+                existing_parent: existing_parent.clone(),
+                existing_authority,
+                new_parent: parent.to_string(),
+                new_authority: authority.to_string()
+            });
+        }
+        if self.ancestors(parent).contains(&child) {
+            return Err(TfError::LoopDetected{
+                parent: parent.to_string(),
+                child: child.to_string(),
+                authority: authority.to_string()
+            });
+        }
+        Ok(())
+    }
+ 
+    /// Walks up the tree from `frame` to its root. The returned list starts with `frame` itself. This always ends 
+    /// because `check_new_edge` never lets a loop into the tree.
+    fn ancestors<'a>(&'a self, frame: &'a str) -> Vec<&'a str> {
+        let mut res = vec!(frame);
+        let mut current = frame;
+        while let Some(parent) = self.parent_transform_index.get(current) {
+            res.push(parent.as_str());
+            current = parent;
+        }
+        res
+    }
+
+    /// Retrieves the transform path. Both frames are walked up to their lowest common ancestor, the path 
+    /// goes from `from` up to that ancestor and then down to `to`.
+    fn retrieve_transform_path(&self, from: String, to: String) -> Result<Vec<String>, TfError> {
+        if from == to {
+            return Ok(vec!());
+        }
+        let from_ancestors = self.ancestors(&from);
+        let to_ancestors = self.ancestors(&to);
+
+        // Both lists end at the root of their tree, so strip the common tail to find the lowest common ancestor.
+        let mut common = 0;
+        while common < from_ancestors.len() && common < to_ancestors.len() && 
+            from_ancestors[from_ancestors.len() - 1 - common] == to_ancestors[to_ancestors.len() - 1 - common] {
+            common += 1;
+        }
+        if common == 0 {
+            return Err(self.could_not_find_transform(&from, &to));
+        }
+
+        let up = &from_ancestors[1..from_ancestors.len() - common + 1];
+        let down = &to_ancestors[..to_ancestors.len() - common];
+        let mut res = Vec::with_capacity(up.len() + down.len());
+        res.extend(up.iter().map(|frame| frame.to_string()));
+        res.extend(down.iter().rev().map(|frame| frame.to_string()));
+        Ok(res)
+    }
+
+    /// Builds the error for two frames which are not connected, naming the root of the tree each frame is part of.
+    fn could_not_find_transform(&self, from: &str, to: &str) -> TfError {
+        TfError::CouldNotFindTransform{
+            from: from.to_string(),
+            to: to.to_string(),
+            from_root: self.ancestors(from).last().unwrap().to_string(),
+            to_root: self.ancestors(to).last().unwrap().to_string()
+        }
+    }
+
+    /// Finds the time cache of the edge between two adjacent frames. The flag is true if going from `first` to 
+    /// `second` means going from a child to its parent, as only the parent to child direction is stored.
+    fn get_edge(&self, first: &str, second: &str) -> Option<(&TfIndividualTransformChain, bool)> {
+        let node = TfGraphNode{child: second.to_string(), parent: first.to_string()};
+        if let Some(time_cache) = self.transform_data.get(&node) {
+            return Some((time_cache, false));
+        }
+        let node = TfGraphNode{child: first.to_string(), parent: second.to_string()};
+        self.transform_data.get(&node).map(|time_cache| (time_cache, true))
+    }
+
+    /// Settings for a lookup on the edge from `parent` to `child`. If `interpolation` is given it overrides the 
+    /// interpolation mode of the edge.
+    fn edge_lookup_options(&self, parent: &str, child: &str, policy: ExtrapolationPolicy, interpolation: Option<InterpolationMode>) -> EdgeLookupOptions {
+        EdgeLookupOptions {
+            extrapolation: policy,
+            interpolation: interpolation.unwrap_or_else(|| self.interpolation_mode(parent, child)),
+            max_gap: self.max_interpolation_gap(parent, child)
+        }
+    }
+
+    /// Gets the transform across a single edge of the tree, inverting the stored transform when going from a 
+    /// child to its parent. As in tf2 the stored samples are interpolated first and the result inverted afterwards.
+    /// When an edge both rotates and translates this differs slightly from interpolating the inverted samples, which
+    /// is what lookups from a child to its parent used to do. Also returns the extrapolation policy which was applied, and if `hops` is given a 
+    /// description of how the transform was computed is added to it.
+    fn get_edge_transform(&self, first: &str, second: &str, time: rosrust::Time, policy: ExtrapolationPolicy, 
+        interpolation: Option<InterpolationMode>, hops: Option<&mut Vec<TransformHop>>) -> Result<(transforms::Transform, ExtrapolationPolicy), TfError> {
+        match self.get_edge(first, second) {
+            Some((time_cache, inverse)) => {
+                let (parent, child) = if inverse { (second, first) } else { (first, second) };
+                let options = self.edge_lookup_options(parent, child, policy, interpolation);
+                let (tf, samples) = time_cache.get_closest_transform(time, &options)?;
+                if let Some(hops) = hops {
+                    let mut hop = time_cache.hop(&samples);
+                    hop.inverted = inverse;
+                    hops.push(hop);
+                }
+                Ok((if inverse { transforms::invert_transform(&tf) } else { tf }, samples.extrapolation))
+            },
+            None => Err(self.could_not_find_transform(first, second))
+        }
+    }
+
+    /// Gets the latest time at which a transform between the two frames is available, which is the oldest of the 
+    /// latest times of the dynamic edges along the path. If the path is made of static edges only, zero is returned.
+    pub fn get_latest_common_time(&self, from: &str, to: &str) -> Result<rosrust::Time, TfError> {
+        let path = self.retrieve_transform_path(from.to_string(), to.to_string())?;
+        let mut latest: Option<rosrust::Time> = None;
+        let mut first = from;
+        for intermediate in &path {
+            let edge_latest = match self.get_edge(first, intermediate) {
+                Some((time_cache, _)) => time_cache.latest_time(),
+                None => return Err(self.could_not_find_transform(first, intermediate))
+            };
+            if let Some(edge_latest) = edge_latest {
+                latest = match latest {
+                    Some(t) if t < edge_latest => Some(t),
+                    _ => Some(edge_latest)
+                };
+            }
+            first = intermediate.as_str();
+        }
+        Ok(latest.unwrap_or_default())
+    }
+
+    /// A time of zero means the latest common time of the two frames, as in tf2.
+    fn resolve_time(&self, from: &str, to: &str, time: rosrust::Time) -> Result<rosrust::Time, TfError> {
+        if time == rosrust::Time::new() {
+            return self.get_latest_common_time(from, to);
+        }
+        Ok(time)
+    }
+
+    /// Looks up a transform within the tree at a given time. A time of zero looks up the transform at the latest
+    /// time at which it is available. Lookups outside of the available data follow the buffer's extrapolation policy.
+    pub fn lookup_transform(&self, from: &str, to: &str, time: rosrust::Time) -> Result<msg::geometry_msgs::TransformStamped,TfError> {
+        self.lookup(from, to, time, self.extrapolation_policy, None, None).map(|x| x.transform)
+    }
+
+    /// Looks up a transform within the tree at a given time, using `policy` instead of the buffer's extrapolation
+    /// policy. The result reports which policy had to be applied.
+    pub fn lookup_transform_with_policy(&self, from: &str, to: &str, time: rosrust::Time, policy: ExtrapolationPolicy) -> Result<ExtrapolatedTransform,TfError> {
+        self.lookup(from, to, time, policy, None, None)
+    }
+
+    /// Looks up a transform within the tree at a given time, also returning which samples, interpolation weights 
+    /// and authorities were used for every edge along the path.
+    pub fn lookup_transform_detailed(&self, from: &str, to: &str, time: rosrust::Time) -> Result<DetailedTransform,TfError> {
+        let mut hops = Vec::new();
+        let res = self.lookup(from, to, time, self.extrapolation_policy, None, Some(&mut hops))?;
+        Ok(DetailedTransform{transform: res.transform, hops})
+    }
+
+    /// Looks up a transform within the tree at a given time, interpolating every edge along the path using `mode`
+    /// instead of the edge's own interpolation mode.
+    pub fn lookup_transform_with_interpolation(&self, from: &str, to: &str, time: rosrust::Time, mode: InterpolationMode) -> Result<msg::geometry_msgs::TransformStamped,TfError> {
+        self.lookup(from, to, time, self.extrapolation_policy, Some(mode), None).map(|x| x.transform)
+    }
+
+    /// Composes the transforms along the path between the two frames. Describing every edge costs allocations, so
+    /// it is only done if `hops` is given.
+    fn lookup(&self, from: &str, to: &str, time: rosrust::Time, policy: ExtrapolationPolicy, 
+        interpolation: Option<InterpolationMode>, mut hops: Option<&mut Vec<TransformHop>>) -> Result<ExtrapolatedTransform,TfError> {
+        let time = self.resolve_time(from, to, time)?;
+        let from = from.to_string();
+        let to = to.to_string();
+        let path = self.retrieve_transform_path(from.clone(), to.clone());
+        
+        match path {
+            Ok(path) => {
+                let mut tflist = Vec::<transforms::Transform>::new();
+                let mut applied = ExtrapolationPolicy::Strict;
+                let mut first = from.as_str();
+                for intermediate in &path {
+                    let tf = self.get_edge_transform(first, intermediate, time, policy, interpolation, hops.as_deref_mut());
+                    match tf {
+                        Err(e) => return Err(e),
+                        Ok((tf, extrapolation)) => {
+                            tflist.push(tf);
+                            applied = applied.combine(extrapolation);
+                        }
+                    }
+                    first = intermediate;
+                }
+                let final_tf = transforms::chain_transforms(&tflist);
+                let msg = msg::geometry_msgs::TransformStamped {
+                    child_frame_id: to.clone(),
+                    header: msg::std_msgs::Header {
+                        frame_id: from.clone(), 
+                        stamp: time,
+                        seq: 1
+                    },
+                    transform: msg::geometry_msgs::Transform{
+                        rotation: msg::geometry_msgs::Quaternion{
+                            x: final_tf.orientation.x, y: final_tf.orientation.y, z: final_tf.orientation.z, w: final_tf.orientation.w
+                        },
+                        translation: msg::geometry_msgs::Vector3{
+                            x: final_tf.position.x, y: final_tf.position.y, z: final_tf.position.z
+                        }
+                    }
+                };
+                return Ok(ExtrapolatedTransform{transform: msg, applied})
+            },
+            Err(x) => return Err(x)
+        }; 
+    }
+
+    /// Checks whether a transform between the two frames can be looked up at the given time, without composing
+    /// the transforms along the path. On failure the error explains which part of the lookup is not possible: 
+    /// either the frames are in disconnected trees or an edge along the path has no data at the given time.
+    pub fn can_transform(&self, from: &str, to: &str, time: rosrust::Time) -> Result<(), TfError> {
+        let time = self.resolve_time(from, to, time)?;
+        let path = self.retrieve_transform_path(from.to_string(), to.to_string())?;
+        let mut first = from;
+        for intermediate in &path {
+            match self.get_edge(first, intermediate) {
+                Some((time_cache, inverse)) => {
+                    let (parent, child) = if inverse { (intermediate.as_str(), first) } else { (first, intermediate.as_str()) };
+                    let options = self.edge_lookup_options(parent, child, self.extrapolation_policy, None);
+                    time_cache.can_transform(time, &options)?
+                },
+                None => return Err(self.could_not_find_transform(first, intermediate))
+            }
+            first = intermediate.as_str();
+        }
+        Ok(())
+    }
+
+    /// Looks up the transform between `from` at `time1` and `to` at `time2` using `fixed_frame` as a 
+    /// frame which does not change over time.
+    pub fn lookup_transform_with_time_travel(&self, to: &str, time2: rosrust::Time,from: &str, time1: rosrust::Time,  fixed_frame: &str) ->  Result<msg::geometry_msgs::TransformStamped,TfError> {
+        let tf1 = self.lookup_transform(from, fixed_frame, time1);
+        let tf2 = self.lookup_transform(to, fixed_frame, time2);
+        match tf1 {Err(x) => return Err(x), Ok(_)=>{}}
+        let tf1 = to_transform(&tf1.unwrap());
+        match tf2 {Err(x) => return Err(x), Ok(_)=>{}}
+        let tf2 = to_transform(&tf2.unwrap());
+        let transforms = transforms::invert_transform(&tf1);
+        let result = transforms::chain_transforms(&vec!(tf2, transforms));
+        Ok(to_transform_stamped(result, from.to_string(), to.to_string(), time1))
+    }
+
+    /// Checks whether the frame is part of the tree, either as the parent or as the child of an edge.
+    pub fn frame_exists(&self, frame: &str) -> bool {
+        self.parent_transform_index.contains_key(frame) || self.child_transform_index.contains_key(frame)
+    }
+
+    /// Gets the parent of a frame. Roots of the tree and unknown frames have none.
+    pub fn get_parent(&self, frame: &str) -> Option<String> {
+        self.parent_transform_index.get(frame).cloned()
+    }
+
+    /// Gets the children of a frame, sorted by name.
+    pub fn get_children(&self, frame: &str) -> Vec<String> {
+        let mut res: Vec<String> = match self.child_transform_index.get(frame) {
+            Some(children) => children.iter().cloned().collect(),
+            None => Vec::new()
+        };
+        res.sort();
+        res
+    }
+
+    /// Gets every frame in the tree, sorted by name.
+    pub fn all_frames(&self) -> Vec<String> {
+        let frames: HashSet<&String> = self.parent_transform_index.keys().chain(self.child_transform_index.keys()).collect();
+        let mut res: Vec<String> = frames.into_iter().cloned().collect();
+        res.sort();
+        res
+    }
+
+    /// Gets the frames a lookup from `from` to `to` passes through. The path goes up the tree from `from` to the 
+    /// lowest common ancestor of both frames and then down to `to`. It does not include `from` but ends with `to`, 
+    /// so it is empty if both frames are the same.
+    pub fn chain(&self, from: &str, to: &str) -> Result<Vec<String>, TfError> {
+        self.retrieve_transform_path(from.to_string(), to.to_string())
+    }
+
+    /// Gets the stamps of the oldest and newest transforms stored on the edge from `parent` to `child`.
+    pub fn edge_time_range(&self, parent: &str, child: &str) -> Option<(rosrust::Time, rosrust::Time)> {
+        let node = TfGraphNode{child: child.to_string(), parent: parent.to_string()};
+        self.transform_data.get(&node).map(|data| {
+            let info = data.edge_info();
+            (info.oldest, info.latest)
+        })
+    }
+
+    /// Statistics about every edge of the tree, sorted by parent and then by child frame.
+    pub fn edges(&self) -> Vec<TfEdgeInfo> {
+        let mut res = Vec::new();
+        for (parent, children) in &self.child_transform_index {
+            for child in children {
+                let node = TfGraphNode{child: child.clone(), parent: parent.clone()};
+                if let Some(data) = self.transform_data.get(&node) {
+                    res.push(data.edge_info());
+                }
+            }
+        }
+        res.sort_by(|a, b| (&a.parent, &a.child).cmp(&(&b.parent, &b.child)));
+        res
+    }
+
+    /// Exports the tree in the Graphviz DOT format. Like tf2's `view_frames`, every edge is labelled with the 
+    /// node publishing it, its average rate, its most recent transform and how much history is stored.
+    pub fn all_frames_as_dot(&self) -> String {
+        let mut res = String::from("digraph G {\n");
+        for edge in self.edges() {
+            res.push_str(&format!(
+                "\"{}\" -> \"{}\"[label=\"Broadcaster: {}\\nAverage rate: {:.3} Hz\\nMost recent transform: {:.3}\\nBuffer length: {:.3} sec\\n{}\\n\"];\n",
+                dot_escape(&edge.parent),
+                dot_escape(&edge.child),
+                dot_escape(&edge.authority),
+                edge.average_rate(),
+                edge.latest.seconds(),
+                edge.buffer_length().seconds(),
+                if edge.static_tf { "Static" } else { "Dynamic" }
+            ));
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    /// Describes every frame which has a parent in the same YAML format as tf2's `allFramesAsYAML`.
+    pub fn all_frames_as_yaml(&self) -> String {
+        self.frames_as_yaml(None)
+    }
+
+    /// Describes every frame which has a parent in the same YAML format as tf2's `allFramesAsYAML`, including how
+    /// old the most recent transform of each frame is at `current_time`.
+    pub fn all_frames_as_yaml_at(&self, current_time: rosrust::Time) -> String {
+        self.frames_as_yaml(Some(current_time))
+    }
+
+    fn frames_as_yaml(&self, current_time: Option<rosrust::Time>) -> String {
+        let edges = self.edges();
+        if edges.is_empty() {
+            return "[]".to_string();
+        }
+        let mut res = String::new();
+        for edge in edges {
+            // Like tf2, static edges report their transforms as valid from time zero
+            let (latest, oldest) = if edge.static_tf { (0f64, 0f64) } else { (edge.latest.seconds(), edge.oldest.seconds()) };
+            res.push_str(&format!("{}: \n", edge.child));
+            res.push_str(&format!("  parent: '{}'\n", edge.parent));
+            res.push_str(&format!("  broadcaster: '{}'\n", edge.authority));
+            res.push_str(&format!("  rate: {:.3}\n", edge.average_rate()));
+            res.push_str(&format!("  most_recent_transform: {:.3}\n", latest));
+            res.push_str(&format!("  oldest_transform: {:.3}\n", oldest));
+            if let Some(current_time) = current_time {
+                res.push_str(&format!("  transform_delay: {:.3}\n", current_time.seconds() - latest));
+            }
+            res.push_str(&format!("  buffer_length: {:.3}\n", latest - oldest));
+        }
+        res
+    }
+
+    /// Lists every frame which has a parent in the same format as tf2's `allFramesAsString`.
+    pub fn all_frames_as_string(&self) -> String {
+        self.edges().iter()
+            .map(|edge| format!("Frame {} exists with parent {}.\n", edge.child, edge.parent))
+            .collect()
+    }
+
+    /// Writes every transform in the buffer along with the authority and type of its edge, so that the buffer 
+    /// can be restored later using `load_snapshot`.
+    pub fn save_snapshot<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        SNAPSHOT_HEADER.to_string().encode(&mut w)?;
+        self.cache_duration.encode(&mut w)?;
+        let edges = self.edges();
+        (edges.len() as u32).encode(&mut w)?;
+        for edge in edges {
+            let node = TfGraphNode{child: edge.child, parent: edge.parent};
+            let transforms = self.transform_data[&node].samples().cloned().collect();
+            edge.authority.encode(&mut w)?;
+            edge.static_tf.encode(&mut w)?;
+            msg::tf2_msgs::TFMessage{transforms}.encode(&mut w)?;
+        }
+        Ok(())
+    }
+
+    /// Restores a buffer written by `save_snapshot`.
+    pub fn load_snapshot<R: io::Read>(mut r: R) -> io::Result<TfBuffer> {
+        if String::decode(&mut r)? != SNAPSHOT_HEADER {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a transform snapshot"));
+        }
+        let mut buffer = TfBuffer::new_with_duration(rosrust::Duration::decode(&mut r)?);
+        let edges = u32::decode(&mut r)?;
+        for _ in 0..edges {
+            let authority = String::decode(&mut r)?;
+            let static_tf = bool::decode(&mut r)?;
+            let msg = msg::tf2_msgs::TFMessage::decode(&mut r)?;
+            for transform in &msg.transforms {
+                buffer.add_transform(transform, &authority, static_tf, None)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+/// Escapes a string for use inside a quoted DOT identifier.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Default for TfBuffer {
+    fn default() -> Self {
+        TfBuffer::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_transform;
+    /// This function builds a tree consisting of the following items:
+    /// * a world coordinate frame
+    /// * an item in the world frame at (1,0,0)
+    /// * base_link of a robot starting at (0,0,0) and progressing at (0,t,0) where t is time in seconds
+    /// * a camera which is (0.5, 0, 0) from the base_link  
+    fn build_test_tree(buffer: &mut TfBuffer, time: f64) {
+        buffer.set_transform(&test_transform("world", "item", time, [1f64, 0f64, 0f64]), "test", true).unwrap();
+        buffer.set_transform(&test_transform("world", "base_link", time, [0f64, time, 0f64]), "test", false).unwrap();
+        buffer.set_transform(&test_transform("base_link", "camera", time, [0.5f64, 0f64, 0f64]), "test", true).unwrap();
+    }
+
+
+    /// Tests a basic lookup
+    #[test]
+    fn test_basic_tf_lookup() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        let res = tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec:0, nsec:0});
+        let expected = msg::geometry_msgs::TransformStamped {
+            child_frame_id: "item".to_string(),
+            header: msg::std_msgs::Header {
+                frame_id: "camera".to_string(), 
+                stamp: rosrust::Time{sec:0, nsec:0},
+                seq: 1
+            },
+            transform: msg::geometry_msgs::Transform{
+                rotation: msg::geometry_msgs::Quaternion{
+                    x: 0f64, y: 0f64, z: 0f64, w: 1f64
+                },
+                translation: msg::geometry_msgs::Vector3{
+                    x: 0.5f64, y: 0f64, z: 0f64
+                }
+            }
+        };
+        assert_eq!(res.unwrap(), expected);
+    }
+
+    /// Tests an interpolated lookup. 
+    #[test]
+    fn test_basic_tf_interpolation() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let res = tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec:0, nsec:700_000_000});
+        let expected = msg::geometry_msgs::TransformStamped {
+            child_frame_id: "item".to_string(),
+            header: msg::std_msgs::Header {
+                frame_id: "camera".to_string(), 
+                stamp: rosrust::Time{sec:0, nsec:700_000_000},
+                seq: 1
+            },
+            transform: msg::geometry_msgs::Transform{
+                rotation: msg::geometry_msgs::Quaternion{
+                    x: 0f64, y: 0f64, z: 0f64, w: 1f64
+                },
+                translation: msg::geometry_msgs::Vector3{
+                    x: 0.5f64, y: -0.7f64, z: 0f64
+                }
+            }
+        };
+        assert_eq!(res.unwrap(), expected);
+    }
+
+    /// Adds an edge from world to base_link which moves 1 along X while making a quarter turn about Z, between 
+    /// times 0 and 1.
+    fn build_rotating_edge(buffer: &mut TfBuffer) {
+        let half_angle = std::f64::consts::FRAC_PI_4;
+        let mut turned = test_transform("world", "base_link", 1f64, [1f64, 0f64, 0f64]);
+        turned.transform.rotation = msg::geometry_msgs::Quaternion{x: 0f64, y: 0f64, z: half_angle.sin(), w: half_angle.cos()};
+        buffer.set_transform(&test_transform("world", "base_link", 0f64, [0f64; 3]), "test", false).unwrap();
+        buffer.set_transform(&turned, "test", false).unwrap();
+    }
+
+    /// Tests that rotation and translation are interpolated the same way on an edge which does both.
+    #[test]
+    fn test_rotating_edge_interpolation() {
+        let mut tf_buffer = TfBuffer::new();
+        build_rotating_edge(&mut tf_buffer);
+        let res = tf_buffer.lookup_transform("world", "base_link", rosrust::Time{sec:0, nsec:250_000_000}).unwrap();
+        // A quarter of the way is 0.25 along X and a sixteenth of a turn
+        let half_angle = std::f64::consts::PI / 16f64;
+        assert!((res.transform.translation.x - 0.25f64).abs() < 1e-9);
+        assert!(res.transform.translation.y.abs() < 1e-9);
+        assert!((res.transform.rotation.z - half_angle.sin()).abs() < 1e-9);
+        assert!((res.transform.rotation.w - half_angle.cos()).abs() < 1e-9);
+    }
+
+    /// Tests that going from a child to its parent interpolates the stored transform and then inverts it.
+    #[test]
+    fn test_rotating_edge_inverted() {
+        let mut tf_buffer = TfBuffer::new();
+        build_rotating_edge(&mut tf_buffer);
+        let res = tf_buffer.lookup_transform("base_link", "world", rosrust::Time{sec:0, nsec:250_000_000}).unwrap();
+        // The inverse of moving 0.25 along X and turning a sixteenth of a turn
+        let angle = std::f64::consts::PI / 8f64;
+        assert!((res.transform.translation.x + 0.25f64 * angle.cos()).abs() < 1e-9);
+        assert!((res.transform.translation.y - 0.25f64 * angle.sin()).abs() < 1e-9);
+        assert!((res.transform.rotation.z + (angle / 2f64).sin()).abs() < 1e-9);
+        assert!((res.transform.rotation.w - (angle / 2f64).cos()).abs() < 1e-9);
+    }
+
+    /// Tests an interpolated lookup. 
+    #[test]
+    fn test_basic_tf_timetravel() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let res = tf_buffer.lookup_transform_with_time_travel("camera", rosrust::Time{sec:0, nsec: 400_000_000}, "camera", rosrust::Time{sec:0, nsec: 700_000_000}, "item");
+        let expected = msg::geometry_msgs::TransformStamped {
+            child_frame_id: "camera".to_string(),
+            header: msg::std_msgs::Header {
+                frame_id: "camera".to_string(), 
+                stamp: rosrust::Time{sec:0, nsec:700_000_000},
+                seq: 0
+            },
+            transform: msg::geometry_msgs::Transform{
+                rotation: msg::geometry_msgs::Quaternion{
+                    x: 0f64, y: 0f64, z: 0f64, w: 1f64
+                },
+                translation: msg::geometry_msgs::Vector3{
+                    x: 0f64, y: 0.3f64, z: 0f64
+                }
+            }
+        };
+        assert_approx_eq(res.unwrap(), expected);
+    }
+
+    /// Tests that `can_transform` reflects the contents of the buffer and that `clear` empties it.
+    #[test]
+    fn test_can_transform_and_clear() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        assert!(tf_buffer.can_transform("camera", "item", rosrust::Time{sec:0, nsec:500_000_000}).is_ok());
+        assert!(tf_buffer.can_transform("camera", "unknown", rosrust::Time{sec:0, nsec:500_000_000}).is_err());
+        tf_buffer.clear();
+        assert!(tf_buffer.can_transform("camera", "item", rosrust::Time{sec:0, nsec:500_000_000}).is_err());
+    }
+
+    /// Tests that old transforms are evicted based on time rather than on the number of samples.
+    #[test]
+    fn test_cache_duration_eviction() {
+        let mut tf_buffer = TfBuffer::new_with_duration(rosrust::Duration{sec: 2, nsec: 0});
+        for i in 0..200 {
+            build_test_tree(&mut tf_buffer, i as f64 * 0.05);
+        }
+        // 200 samples at 20Hz span almost 10 seconds, of which only the last 2 seconds are kept.
+        assert!(tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec: 8, nsec: 0}).is_ok());
+        assert!(tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec: 7, nsec: 0}).is_err());
+    }
+
+    /// Tests that transforms arriving out of order are still looked up correctly.
+    #[test]
+    fn test_out_of_order_insertion() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 1f64);
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 0.5f64);
+        let res = tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec:0, nsec:700_000_000});
+        assert!((res.unwrap().transform.translation.y + 0.7f64).abs() < 1e-9);
+    }
+
+    /// Tests that each transform is stored once and that edges can be traversed in both directions.
+    #[test]
+    fn test_edges_stored_once() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        assert_eq!(tf_buffer.transform_data.len(), 3);
+        let res = tf_buffer.lookup_transform("item", "camera", rosrust::Time{sec:0, nsec:700_000_000}).unwrap();
+        assert!((res.transform.translation.x + 0.5f64).abs() < 1e-9);
+        assert!((res.transform.translation.y - 0.7f64).abs() < 1e-9);
+    }
+
+    /// Tests that the path goes up to the lowest common ancestor and back down.
+    #[test]
+    fn test_transform_path() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        let path = tf_buffer.retrieve_transform_path("camera".to_string(), "item".to_string()).unwrap();
+        assert_eq!(path, vec!("base_link", "world", "item"));
+        let path = tf_buffer.retrieve_transform_path("world".to_string(), "camera".to_string()).unwrap();
+        assert_eq!(path, vec!("base_link", "camera"));
+        let path = tf_buffer.retrieve_transform_path("camera".to_string(), "base_link".to_string()).unwrap();
+        assert_eq!(path, vec!("base_link"));
+        assert!(tf_buffer.retrieve_transform_path("camera".to_string(), "unknown".to_string()).is_err());
+    }
+
+    /// Tests that transforms which would give a frame a second parent or create a loop are rejected.
+    #[test]
+    fn test_reject_invalid_edges() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+
+        let mut second_parent = tf_buffer.lookup_transform("item", "camera", rosrust::Time{sec:0, nsec:0}).unwrap();
+        second_parent.header.frame_id = "item".to_string();
+        match tf_buffer.set_transform(&second_parent, "intruder", false) {
+            Err(TfError::MultipleParents{existing_parent, existing_authority, new_parent, new_authority, ..}) => {
+                assert_eq!(existing_parent, "base_link");
+                assert_eq!(existing_authority, "test");
+                assert_eq!(new_parent, "item");
+                assert_eq!(new_authority, "intruder");
+            },
+            x => panic!("Expected MultipleParents, got {:?}", x)
+        }
+
+        let mut back_to_root = second_parent.clone();
+        back_to_root.header.frame_id = "camera".to_string();
+        back_to_root.child_frame_id = "world".to_string();
+        match tf_buffer.set_transform(&back_to_root, "intruder", false) {
+            Err(TfError::LoopDetected{..}) => {},
+            x => panic!("Expected LoopDetected, got {:?}", x)
+        }
+
+        // The tree is left untouched
+        let path = tf_buffer.retrieve_transform_path("camera".to_string(), "item".to_string()).unwrap();
+        assert_eq!(path, vec!("base_link", "world", "item"));
+    }
+
+    /// Tests that `can_transform` explains why a transform is not available.
+    #[test]
+    fn test_can_transform_diagnostics() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        match tf_buffer.can_transform("camera", "item", rosrust::Time{sec:2, nsec:0}) {
+            Err(TfError::AttemptedLookUpInFuture{parent, child, ..}) => {
+                assert_eq!(parent, "world");
+                assert_eq!(child, "base_link");
+            },
+            x => panic!("Expected AttemptedLookUpInFuture, got {:?}", x)
+        }
+
+        let mut other_tree = tf_buffer.lookup_transform("world", "item", rosrust::Time{sec:0, nsec:0}).unwrap();
+        other_tree.header.frame_id = "map".to_string();
+        other_tree.child_frame_id = "landmark".to_string();
+        tf_buffer.set_transform(&other_tree, "test", true).unwrap();
+        let err = tf_buffer.can_transform("camera", "landmark", rosrust::Time{sec:0, nsec:0}).unwrap_err();
+        let explanation = format!("{}", err);
+        assert!(explanation.contains("'world'"));
+        assert!(explanation.contains("'map'"));
+    }
+
+    /// Tests that lookup errors report the requested time and the range of data available on the edge.
+    #[test]
+    fn test_error_time_range() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 1f64);
+        build_test_tree(&mut tf_buffer, 2f64);
+        match tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec:0, nsec:500_000_000}) {
+            Err(TfError::AttemptedLookupInPast{parent, child, time, earliest, latest}) => {
+                assert_eq!(parent, "world");
+                assert_eq!(child, "base_link");
+                assert_eq!(time, rosrust::Time{sec:0, nsec:500_000_000});
+                assert_eq!(earliest, rosrust::Time{sec:1, nsec:0});
+                assert_eq!(latest, rosrust::Time{sec:2, nsec:0});
+            },
+            x => panic!("Expected AttemptedLookupInPast, got {:?}", x)
+        }
+        let err = tf_buffer.lookup_transform("camera", "item", rosrust::Time{sec:3, nsec:0}).unwrap_err();
+        assert_eq!(format!("{}", err), "Lookup would require extrapolation into the future. Requested time 3.000000000 but the latest \
+            data is at time 2.000000000 (earliest data at time 1.000000000), when looking up transform from frame [world] to frame [base_link]");
+    }
+
+    /// Tests that a time of zero looks up the transform at the latest common time.
+    #[test]
+    fn test_latest_common_time() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 1f64);
+        build_test_tree(&mut tf_buffer, 2f64);
+        assert_eq!(tf_buffer.get_latest_common_time("camera", "item").unwrap(), rosrust::Time{sec:2, nsec:0});
+        assert_eq!(tf_buffer.get_latest_common_time("camera", "base_link").unwrap(), rosrust::Time::new());
+        let res = tf_buffer.lookup_transform("camera", "item", rosrust::Time::new()).unwrap();
+        assert_eq!(res.header.stamp, rosrust::Time{sec:2, nsec:0});
+        assert!((res.transform.translation.y + 2f64).abs() < 1e-9);
+    }
+
+    /// Tests the different extrapolation policies.
+    #[test]
+    fn test_extrapolation_policy() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let time = rosrust::Time{sec:1, nsec:20_000_000};
+        let max = rosrust::Duration{sec:0, nsec:50_000_000};
+        assert!(tf_buffer.lookup_transform("camera", "item", time).is_err());
+
+        let res = tf_buffer.lookup_transform_with_policy("camera", "item", time, ExtrapolationPolicy::Bounded(max)).unwrap();
+        assert_eq!(res.applied, ExtrapolationPolicy::Bounded(max));
+        assert!((res.transform.transform.translation.y + 1f64).abs() < 1e-9);
+
+        let res = tf_buffer.lookup_transform_with_policy("camera", "item", time, ExtrapolationPolicy::ConstantVelocity(max)).unwrap();
+        assert_eq!(res.applied, ExtrapolationPolicy::ConstantVelocity(max));
+        assert!((res.transform.transform.translation.y + 1.02f64).abs() < 1e-9);
+
+        let res = tf_buffer.lookup_transform_with_policy("camera", "item", rosrust::Time{sec:0, nsec:500_000_000}, ExtrapolationPolicy::ConstantVelocity(max)).unwrap();
+        assert_eq!(res.applied, ExtrapolationPolicy::Strict);
+
+        let too_late = rosrust::Time{sec:1, nsec:100_000_000};
+        assert!(tf_buffer.lookup_transform_with_policy("camera", "item", too_late, ExtrapolationPolicy::ConstantVelocity(max)).is_err());
+
+        tf_buffer.set_extrapolation_policy(ExtrapolationPolicy::Bounded(max));
+        assert!(tf_buffer.can_transform("camera", "item", time).is_ok());
+        assert!(tf_buffer.lookup_transform("camera", "item", time).is_ok());
+    }
+
+    /// Tests that constant velocity extrapolation of an edge with a single sample is reported as holding it.
+    #[test]
+    fn test_extrapolation_single_sample() {
+        let max = rosrust::Duration{sec:0, nsec:50_000_000};
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 1f64);
+        let res = tf_buffer.lookup_transform_with_policy("camera", "item", rosrust::Time{sec:1, nsec:20_000_000}, 
+            ExtrapolationPolicy::ConstantVelocity(max)).unwrap();
+        assert_eq!(res.applied, ExtrapolationPolicy::Bounded(max));
+        assert!((res.transform.transform.translation.y + 1f64).abs() < 1e-9);
+    }
+
+    /// Tests that constant velocity extrapolation skips samples with the same stamp instead of dividing by zero.
+    #[test]
+    fn test_extrapolation_repeated_stamps() {
+        let time = rosrust::Time{sec:1, nsec:20_000_000};
+        let policy = ExtrapolationPolicy::ConstantVelocity(rosrust::Duration{sec:0, nsec:50_000_000});
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let res = tf_buffer.lookup_transform_with_policy("camera", "item", time, policy).unwrap();
+        assert!((res.transform.transform.translation.y + 1.02f64).abs() < 1e-9);
+
+        // Without a second stamp there is no velocity, so the closest transform is held
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 1f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let res = tf_buffer.lookup_transform_with_policy("camera", "item", time, policy).unwrap();
+        assert!((res.transform.transform.translation.y + 1f64).abs() < 1e-9);
+        assert_eq!(res.applied, ExtrapolationPolicy::Bounded(rosrust::Duration{sec:0, nsec:50_000_000}));
+    }
+
+    /// Tests choosing the interpolation mode per edge and per lookup.
+    #[test]
+    fn test_interpolation_modes() {
+        let mut tf_buffer = TfBuffer::new();
+        for i in 0..4 {
+            build_test_tree(&mut tf_buffer, i as f64);
+        }
+        let time = rosrust::Time{sec:1, nsec:700_000_000};
+        let res = tf_buffer.lookup_transform_with_interpolation("camera", "item", time, InterpolationMode::Nearest).unwrap();
+        assert!((res.transform.translation.y + 2f64).abs() < 1e-9);
+        let res = tf_buffer.lookup_transform_with_interpolation("camera", "item", time, InterpolationMode::CubicSpline).unwrap();
+        assert!((res.transform.translation.y + 1.7f64).abs() < 1e-9);
+
+        tf_buffer.set_interpolation_mode("world", "base_link", InterpolationMode::Hold);
+        assert_eq!(tf_buffer.interpolation_mode("world", "base_link"), InterpolationMode::Hold);
+        assert_eq!(tf_buffer.interpolation_mode("base_link", "camera"), InterpolationMode::Linear);
+        let res = tf_buffer.lookup_transform("camera", "item", time).unwrap();
+        assert!((res.transform.translation.y + 1f64).abs() < 1e-9);
+    }
+
+    /// Tests that lookups across a gap larger than the allowed maximum fail.
+    #[test]
+    fn test_max_interpolation_gap() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 5f64);
+        let time = rosrust::Time{sec:2, nsec:0};
+        assert!(tf_buffer.lookup_transform("camera", "item", time).is_ok());
+
+        tf_buffer.set_max_interpolation_gap(Some(rosrust::Duration{sec:1, nsec:0}));
+        match tf_buffer.lookup_transform("camera", "item", time) {
+            Err(TfError::InterpolationGapExceeded{parent, child, gap, ..}) => {
+                assert_eq!(parent, "world");
+                assert_eq!(child, "base_link");
+                assert_eq!(gap, rosrust::Duration{sec:5, nsec:0});
+            },
+            x => panic!("Expected InterpolationGapExceeded, got {:?}", x)
+        }
+        assert!(tf_buffer.can_transform("camera", "item", time).is_err());
+
+        tf_buffer.set_edge_max_interpolation_gap("world", "base_link", Some(rosrust::Duration{sec:10, nsec:0}));
+        assert!(tf_buffer.lookup_transform("camera", "item", time).is_ok());
+        assert!(tf_buffer.can_transform("camera", "item", time).is_ok());
+    }
+
+    /// Tests that a detailed lookup describes every edge along the path.
+    #[test]
+    fn test_lookup_transform_detailed() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let res = tf_buffer.lookup_transform_detailed("camera", "item", rosrust::Time{sec:0, nsec:700_000_000}).unwrap();
+        assert!((res.transform.transform.translation.y + 0.7f64).abs() < 1e-9);
+        assert_eq!(res.hops.len(), 3);
+
+        let camera = &res.hops[0];
+        assert_eq!((camera.parent.as_str(), camera.child.as_str()), ("base_link", "camera"));
+        assert!(camera.inverted && camera.static_tf);
+        assert_eq!(camera.extrapolation, ExtrapolationPolicy::Strict);
+
+        let base_link = &res.hops[1];
+        assert_eq!((base_link.parent.as_str(), base_link.child.as_str()), ("world", "base_link"));
+        assert!(base_link.inverted && !base_link.static_tf);
+        assert_eq!(base_link.extrapolation, ExtrapolationPolicy::Strict);
+        assert_eq!(base_link.before, rosrust::Time{sec:0, nsec:0});
+        assert_eq!(base_link.after, rosrust::Time{sec:1, nsec:0});
+        assert!((base_link.weight - 0.7f64).abs() < 1e-9);
+        assert_eq!((base_link.before_authority.as_str(), base_link.after_authority.as_str()), ("test", "test"));
+
+        let item = &res.hops[2];
+        assert_eq!((item.parent.as_str(), item.child.as_str()), ("world", "item"));
+        assert!(!item.inverted && item.static_tf);
+
+        let max = rosrust::Duration{sec:1, nsec:0};
+        tf_buffer.set_extrapolation_policy(ExtrapolationPolicy::Bounded(max));
+        let res = tf_buffer.lookup_transform_detailed("world", "base_link", rosrust::Time{sec:1, nsec:500_000_000}).unwrap();
+        assert_eq!(res.hops[0].extrapolation, ExtrapolationPolicy::Bounded(max));
+    }
+
+    /// Tests that a detailed lookup reports the publishers of the samples which were used, not the latest one.
+    #[test]
+    fn test_lookup_transform_detailed_authorities() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let mut tf = tf_buffer.lookup_transform("world", "base_link", rosrust::Time{sec:1, nsec:0}).unwrap();
+        tf.header.stamp = rosrust::Time{sec:2, nsec:0};
+        tf_buffer.set_transform(&tf, "other", false).unwrap();
+
+        let res = tf_buffer.lookup_transform_detailed("world", "base_link", rosrust::Time{sec:0, nsec:500_000_000}).unwrap();
+        assert_eq!((res.hops[0].before_authority.as_str(), res.hops[0].after_authority.as_str()), ("test", "test"));
+        let res = tf_buffer.lookup_transform_detailed("world", "base_link", rosrust::Time{sec:1, nsec:500_000_000}).unwrap();
+        assert_eq!((res.hops[0].before_authority.as_str(), res.hops[0].after_authority.as_str()), ("test", "other"));
+    }
+
+    /// Tests the queries about the structure of the tree.
+    #[test]
+    fn test_graph_queries() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        assert!(tf_buffer.frame_exists("world"));
+        assert!(tf_buffer.frame_exists("camera"));
+        assert!(!tf_buffer.frame_exists("map"));
+
+        assert_eq!(tf_buffer.get_parent("camera"), Some("base_link".to_string()));
+        assert_eq!(tf_buffer.get_parent("world"), None);
+        assert_eq!(tf_buffer.get_children("world"), vec!["base_link".to_string(), "item".to_string()]);
+        assert!(tf_buffer.get_children("camera").is_empty());
+        assert_eq!(tf_buffer.all_frames(), vec!["base_link", "camera", "item", "world"]);
+
+        assert_eq!(tf_buffer.chain("camera", "item").unwrap(), vec!["base_link", "world", "item"]);
+        assert!(tf_buffer.chain("camera", "camera").unwrap().is_empty());
+        assert!(tf_buffer.chain("camera", "map").is_err());
+
+        assert_eq!(tf_buffer.edge_time_range("world", "base_link"), Some((rosrust::Time{sec: 0, nsec: 0}, rosrust::Time{sec: 1, nsec: 0})));
+        assert_eq!(tf_buffer.edge_time_range("base_link", "world"), None);
+    }
+
+    /// Tests the statistics reported for every edge and the DOT export.
+    #[test]
+    fn test_edges_and_dot() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 0.5f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let edges = tf_buffer.edges();
+        let names: Vec<(&str, &str)> = edges.iter().map(|x| (x.parent.as_str(), x.child.as_str())).collect();
+        assert_eq!(names, vec![("base_link", "camera"), ("world", "base_link"), ("world", "item")]);
+
+        let base_link = &edges[1];
+        assert_eq!(base_link.authority, "test");
+        assert!(!base_link.static_tf);
+        assert_eq!(base_link.samples, 3);
+        assert_eq!(base_link.oldest, rosrust::Time{sec: 0, nsec: 0});
+        assert_eq!(base_link.latest, rosrust::Time{sec: 1, nsec: 0});
+        assert!((base_link.average_rate() - 3f64).abs() < 1e-9);
+        assert!(edges[0].static_tf);
+        assert_eq!(edges[0].samples, 1);
+
+        let dot = tf_buffer.all_frames_as_dot();
+        assert!(dot.starts_with("digraph G {\n"));
+        assert!(dot.contains("\"world\" -> \"base_link\"[label=\"Broadcaster: test\\nAverage rate: 3.000 Hz\\nMost recent transform: 1.000\\nBuffer length: 1.000 sec\\nDynamic\\n\"];\n"));
+        assert!(dot.contains("\"base_link\" -> \"camera\""));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    /// Tests that the delay between the stamp and the receive time of transforms is measured.
+    #[test]
+    fn test_receive_delay() {
+        let mut tf_buffer = TfBuffer::new();
+        build_test_tree(&mut tf_buffer, 0f64);
+        assert_eq!(tf_buffer.edges()[1].average_delay, None);
+
+        let mut msg = tf_buffer.lookup_transform("world", "base_link", rosrust::Time::new()).unwrap();
+        for (stamp, received) in [(1u32, 100_000_000u32), (2u32, 300_000_000u32)].iter() {
+            msg.header.stamp = rosrust::Time{sec: *stamp, nsec: 0};
+            let errors = tf_buffer.handle_incoming_transforms(msg::tf2_msgs::TFMessage{transforms: vec![msg.clone()]}, false, "node", 
+                rosrust::Time{sec: *stamp, nsec: *received});
+            assert!(errors.is_empty());
+        }
+        let edges = tf_buffer.edges();
+        assert_eq!(edges[1].child, "base_link");
+        assert_eq!(edges[1].authority, "node");
+        assert_eq!(edges[1].average_delay, Some(rosrust::Duration{sec: 0, nsec: 200_000_000}));
+        assert_eq!(edges[1].max_delay, Some(rosrust::Duration{sec: 0, nsec: 300_000_000}));
+        assert_eq!(edges[0].average_delay, None);
+    }
+
+    /// Tests the tf2 compatible descriptions of the tree.
+    #[test]
+    fn test_all_frames_as_yaml_and_string() {
+        let mut tf_buffer = TfBuffer::new();
+        assert_eq!(tf_buffer.all_frames_as_yaml(), "[]");
+        assert_eq!(tf_buffer.all_frames_as_string(), "");
+
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let expected = "\
+camera: 
+  parent: 'base_link'
+  broadcaster: 'test'
+  rate: 10000.000
+  most_recent_transform: 0.000
+  oldest_transform: 0.000
+  buffer_length: 0.000
+base_link: 
+  parent: 'world'
+  broadcaster: 'test'
+  rate: 2.000
+  most_recent_transform: 1.000
+  oldest_transform: 0.000
+  buffer_length: 1.000
+item: 
+  parent: 'world'
+  broadcaster: 'test'
+  rate: 10000.000
+  most_recent_transform: 0.000
+  oldest_transform: 0.000
+  buffer_length: 0.000
+";
+        assert_eq!(tf_buffer.all_frames_as_yaml(), expected);
+        let yaml = tf_buffer.all_frames_as_yaml_at(rosrust::Time{sec: 1, nsec: 500_000_000});
+        assert!(yaml.contains("  oldest_transform: 0.000\n  transform_delay: 0.500\n  buffer_length: 1.000\n"));
+
+        assert_eq!(tf_buffer.all_frames_as_string(), 
+            "Frame camera exists with parent base_link.\nFrame base_link exists with parent world.\nFrame item exists with parent world.\n");
+    }
+
+    /// Tests that a snapshot restores every edge.
+    #[test]
+    fn test_snapshot() {
+        let mut tf_buffer = TfBuffer::new_with_duration(rosrust::Duration{sec: 5, nsec: 0});
+        build_test_tree(&mut tf_buffer, 0f64);
+        build_test_tree(&mut tf_buffer, 1f64);
+        let mut data = Vec::new();
+        tf_buffer.save_snapshot(&mut data).unwrap();
+
+        let restored = TfBuffer::load_snapshot(data.as_slice()).unwrap();
+        assert_eq!(restored.cache_duration(), tf_buffer.cache_duration());
+        assert_eq!(restored.edges(), tf_buffer.edges());
+        let time = rosrust::Time{sec: 0, nsec: 700_000_000};
+        assert_approx_eq(restored.lookup_transform("camera", "item", time).unwrap(), tf_buffer.lookup_transform("camera", "item", time).unwrap());
+
+        assert!(TfBuffer::load_snapshot(&data[..data.len() - 1]).is_err());
+        assert!(TfBuffer::load_snapshot(&b"not a snapshot"[..]).is_err());
+    }
+
+    fn assert_approx_eq(msg1: msg::geometry_msgs::TransformStamped, msg2: msg::geometry_msgs::TransformStamped) {
+        assert_eq!(msg1.header, msg2.header);
+        assert_eq!(msg1.child_frame_id, msg2.child_frame_id);
+
+        assert!((msg1.transform.rotation.x - msg2.transform.rotation.x).abs() < 1e-9);
+        assert!((msg1.transform.rotation.y - msg2.transform.rotation.y).abs() < 1e-9);
+        assert!((msg1.transform.rotation.z - msg2.transform.rotation.z).abs() < 1e-9);
+        assert!((msg1.transform.rotation.w - msg2.transform.rotation.w).abs() < 1e-9);
+
+        assert!((msg1.transform.translation.x - msg2.transform.translation.x).abs() < 1e-9);
+        assert!((msg1.transform.translation.y - msg2.transform.translation.y).abs() < 1e-9);
+        assert!((msg1.transform.translation.z - msg2.transform.translation.z).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::get_nanos;
+
+/// Statistics about the transforms stored on one edge of the frame tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TfEdgeInfo {
+    pub parent: String,
+    pub child: String,
+    /// Caller id of the node which last published on the edge
+    pub authority: String,
+    /// Set if the edge holds static transforms
+    pub static_tf: bool,
+    /// Number of transforms stored on the edge
+    pub samples: usize,
+    /// Stamp of the oldest transform stored on the edge
+    pub oldest: rosrust::Time,
+    /// Stamp of the newest transform stored on the edge
+    pub latest: rosrust::Time,
+    /// Average time between the stamp of a transform and when it was received. Only transforms received by a 
+    /// `TfListener` have a receive time, and static edges have no delay.
+    pub average_delay: Option<rosrust::Duration>,
+    /// Largest time between the stamp of a transform and when it was received
+    pub max_delay: Option<rosrust::Duration>
+}
+
+impl TfEdgeInfo {
+    /// Time between the oldest and newest transforms stored on the edge.
+    pub fn buffer_length(&self) -> rosrust::Duration {
+        self.latest - self.oldest
+    }
+
+    /// Average rate in Hz at which transforms were published on the edge, computed the same way as tf2 does.
+    /// Edges holding a single transform report 10 kHz.
+    pub fn average_rate(&self) -> f64 {
+        let seconds = get_nanos(self.buffer_length()) as f64 * 1e-9;
+        self.samples as f64 / seconds.max(0.0001)
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::get_nanos;
+
+/// Enumerates the different types of errors
+#[derive(Clone, Debug)]
+pub enum TfError {
+    /// Error due to looking up too far in the past. I.E the information is no longer available in the TF Cache.
+    AttemptedLookupInPast {
+        /// Parent frame of the edge which has no data at the requested time
+        parent: String,
+        /// Child frame of the edge which has no data at the requested time
+        child: String,
+        /// Requested time
+        time: rosrust::Time,
+        /// Oldest data available on the edge
+        earliest: rosrust::Time,
+        /// Newest data available on the edge
+        latest: rosrust::Time
+    },
+    /// Error due ti the transform not yet being available.
+    AttemptedLookUpInFuture {
+        /// Parent frame of the edge which has no data at the requested time
+        parent: String,
+        /// Child frame of the edge which has no data at the requested time
+        child: String,
+        /// Requested time
+        time: rosrust::Time,
+        /// Oldest data available on the edge
+        earliest: rosrust::Time,
+        /// Newest data available on the edge
+        latest: rosrust::Time
+    },
+    /// The transforms on either side of the requested time are too far apart to be interpolated.
+    InterpolationGapExceeded {
+        parent: String,
+        child: String,
+        /// Requested time
+        time: rosrust::Time,
+        /// Time between the transforms on either side of the requested time
+        gap: rosrust::Duration,
+        /// Largest gap allowed on the edge
+        max_gap: rosrust::Duration
+    },
+    /// There is no path between the from and to frame.
+    CouldNotFindTransform {
+        from: String,
+        to: String,
+        /// Root of the tree `from` is part of
+        from_root: String,
+        /// Root of the tree `to` is part of
+        to_root: String
+    },
+    /// In the event that a write is simultaneously happening with a read of the same tf buffer
+    CouldNotAcquireLock,
+    /// The transform was rejected as its child frame already has a different parent. 
+    MultipleParents {
+        child: String,
+        existing_parent: String,
+        /// Authority which published the existing edge
+        existing_authority: String,
+        new_parent: String,
+        /// Authority which published the rejected edge
+        new_authority: String
+    },
+    /// The transform was rejected as it would create a loop in the tree.
+    LoopDetected {
+        parent: String,
+        child: String,
+        /// Authority which published the rejected edge
+        authority: String
+    }
+}
+
+impl fmt::Display for TfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TfError::AttemptedLookupInPast{parent, child, time, earliest, latest} => 
+                write!(f, "Lookup would require extrapolation into the past. Requested time {} but the earliest data is at time {} \
+                    (latest data at time {}), when looking up transform from frame [{}] to frame [{}]", 
+                    TimeDisplay(*time), TimeDisplay(*earliest), TimeDisplay(*latest), parent, child),
+            TfError::AttemptedLookUpInFuture{parent, child, time, earliest, latest} => 
+                write!(f, "Lookup would require extrapolation into the future. Requested time {} but the latest data is at time {} \
+                    (earliest data at time {}), when looking up transform from frame [{}] to frame [{}]", 
+                    TimeDisplay(*time), TimeDisplay(*latest), TimeDisplay(*earliest), parent, child),
+            TfError::InterpolationGapExceeded{parent, child, time, gap, max_gap} => 
+                write!(f, "Lookup at time {} would interpolate across a gap of {:.9} seconds, which exceeds the maximum of {:.9} seconds, \
+                    when looking up transform from frame [{}] to frame [{}]", 
+                    TimeDisplay(*time), seconds(*gap), seconds(*max_gap), parent, child),
+            TfError::CouldNotFindTransform{from, to, from_root, to_root} => 
+                write!(f, "Could not find a connection between '{}' and '{}' because they are not part of the same tree. \
+                    '{}' is part of the tree rooted at '{}' and '{}' is part of the tree rooted at '{}'", from, to, from, from_root, to, to_root),
+            TfError::CouldNotAcquireLock => 
+                write!(f, "Could not acquire a lock on the tf buffer"),
+            TfError::MultipleParents{child, existing_parent, existing_authority, new_parent, new_authority} => 
+                write!(f, "Ignoring transform from [{}] to [{}] published by {}: [{}] already has parent [{}] published by {}", 
+                    new_parent, child, new_authority, child, existing_parent, existing_authority),
+            TfError::LoopDetected{parent, child, authority} => 
+                write!(f, "Ignoring transform from [{}] to [{}] published by {}: it would create a loop in the tf tree", parent, child, authority)
+        }
+    }
+}
+
+impl std::error::Error for TfError {}
+
+fn seconds(duration: rosrust::Duration) -> f64 {
+    get_nanos(duration) as f64 * 1e-9
+}
+
+/// Formats a time as seconds, the same way tf2 does.
+struct TimeDisplay(rosrust::Time);
+
+impl fmt::Display for TimeDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:09}", self.0.sec, self.0.nsec)
+    }
+}
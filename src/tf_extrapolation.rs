@@ -0,0 +1,44 @@
+use crate::msg;
+
+/// Controls what happens when a lookup falls outside of the range of data available on an edge.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExtrapolationPolicy {
+    /// Lookups outside of the available data fail. This is the default.
+    #[default]
+    Strict,
+    /// Lookups up to the given duration outside of the available data use the closest transform on the edge.
+    Bounded(rosrust::Duration),
+    /// Lookups up to the given duration outside of the available data assume the edge keeps moving with the 
+    /// constant linear and angular velocity between its two closest transforms.
+    ConstantVelocity(rosrust::Duration)
+}
+
+impl ExtrapolationPolicy {
+    /// How far outside of the available data lookups may go.
+    pub(crate) fn max_duration(&self) -> Option<rosrust::Duration> {
+        match self {
+            ExtrapolationPolicy::Strict => None,
+            ExtrapolationPolicy::Bounded(max) => Some(*max),
+            ExtrapolationPolicy::ConstantVelocity(max) => Some(*max)
+        }
+    }
+
+    /// Combines the policies applied on two edges into the one reported for a lookup across both. Holding a 
+    /// transform is reported over extrapolating at a constant velocity, which is reported over not extrapolating.
+    pub(crate) fn combine(self, other: ExtrapolationPolicy) -> ExtrapolationPolicy {
+        match (self, other) {
+            (ExtrapolationPolicy::Strict, _) | (_, ExtrapolationPolicy::Bounded(_)) => other,
+            _ => self
+        }
+    }
+}
+
+/// Result of a lookup made with an `ExtrapolationPolicy`.
+#[derive(Clone, Debug)]
+pub struct ExtrapolatedTransform {
+    pub transform: msg::geometry_msgs::TransformStamped,
+    /// The policy which was applied to produce the transform. This is `ExtrapolationPolicy::Strict` if no edge 
+    /// along the path had to be extrapolated. Constant velocity extrapolation holds the nearest transform of edges
+    /// with a single stamp, so it is reported as `ExtrapolationPolicy::Bounded` if any edge was held.
+    pub applied: ExtrapolationPolicy
+}
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once, OnceLock, RwLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Instant;
+
+use crate::msg;
+use crate::tf_buffer::TfBuffer;
+use crate::tf_error::TfError;
+
+/// Source of the ids which identify a future to the buffer and to the deadline timer.
+static NEXT_FUTURE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A transform lookup which resolves once the transform can be computed.
+///
+/// The future is woken whenever transforms are added to the buffer. If the transform is still not available 
+/// at the deadline, it resolves to the error of the last attempted lookup. Deadlines are enforced by a single
+/// timer thread shared by all futures, so the future can be used with any executor. Dropping the future cancels
+/// its deadline.
+///
+/// Example usage:
+///
+/// ```ignore
+/// let tf = listener.lookup_transform_async("camera", "base_link", time, rosrust::Duration::from_seconds(1)).await;
+/// ```
+pub struct TransformFuture {
+    buffer: Arc<RwLock<TfBuffer>>,
+    from: String,
+    to: String,
+    time: rosrust::Time,
+    deadline: Instant,
+    id: u64
+}
+
+impl TransformFuture {
+    /// Creates a lookup of the transform between `from` and `to` at `time` which gives up at `deadline`.
+    pub fn new(buffer: Arc<RwLock<TfBuffer>>, from: &str, to: &str, time: rosrust::Time, deadline: Instant) -> Self {
+        TransformFuture {
+            buffer,
+            from: from.to_string(),
+            to: to.to_string(),
+            time,
+            deadline,
+            id: NEXT_FUTURE_ID.fetch_add(1, Ordering::Relaxed)
+        }
+    }
+}
+
+impl Future for TransformFuture {
+    type Output = Result<msg::geometry_msgs::TransformStamped, TfError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        // The read lock is held from the lookup until the waker is registered so no insertion can be missed.
+        let buffer = this.buffer.read().unwrap();
+        let res = buffer.lookup_transform(&this.from, &this.to, this.time);
+        if res.is_ok() || Instant::now() >= this.deadline {
+            buffer.wakers().unregister(this.id);
+            DeadlineTimer::cancel(this.deadline, this.id);
+            return Poll::Ready(res);
+        }
+        buffer.wakers().register(this.id, cx.waker());
+        drop(buffer);
+        DeadlineTimer::global().schedule(this.deadline, this.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl Drop for TransformFuture {
+    fn drop(&mut self) {
+        if let Ok(buffer) = self.buffer.read() {
+            buffer.wakers().unregister(self.id);
+        }
+        DeadlineTimer::cancel(self.deadline, self.id);
+    }
+}
+
+/// Tasks waiting for transforms to be added to a `TfBuffer`, by future id. The wakers have their own lock so 
+/// that pending futures only need to read the buffer. A clone of a buffer starts without any waiting tasks.
+#[derive(Default)]
+pub(crate) struct TfWakers {
+    wakers: Mutex<HashMap<u64, Waker>>
+}
+
+impl TfWakers {
+    /// Registers the waker of a future, replacing the one registered by an earlier poll unless it wakes the same task.
+    pub(crate) fn register(&self, id: u64, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        match wakers.get(&id) {
+            Some(existing) if existing.will_wake(waker) => {},
+            _ => { wakers.insert(id, waker.clone()); }
+        }
+    }
+
+    pub(crate) fn unregister(&self, id: u64) {
+        self.wakers.lock().unwrap().remove(&id);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.wakers.lock().unwrap().len()
+    }
+
+    /// Wakes all waiting tasks. They register again if they are still waiting when they are polled.
+    pub(crate) fn wake_all(&self) {
+        let wakers: Vec<Waker> = self.wakers.lock().unwrap().drain().map(|(_, waker)| waker).collect();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Clone for TfWakers {
+    fn clone(&self) -> Self {
+        TfWakers::default()
+    }
+}
+
+impl fmt::Debug for TfWakers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TfWakers({} waiting)", self.len())
+    }
+}
+
+static DEADLINE_TIMER: OnceLock<DeadlineTimer> = OnceLock::new();
+static START_DEADLINE_TIMER: Once = Once::new();
+
+/// Wakes futures whose deadline has passed. One thread serves all futures, sleeping until the earliest deadline.
+#[derive(Default)]
+struct DeadlineTimer {
+    /// Wakers by deadline and future id
+    deadlines: Mutex<BTreeMap<(Instant, u64), Waker>>,
+    condvar: Condvar
+}
+
+impl DeadlineTimer {
+    /// Gets the timer, starting its thread on first use.
+    fn global() -> &'static DeadlineTimer {
+        let timer = DEADLINE_TIMER.get_or_init(DeadlineTimer::default);
+        START_DEADLINE_TIMER.call_once(|| {
+            thread::spawn(move || timer.run());
+        });
+        timer
+    }
+
+    /// Wakes the future `id` at `deadline`, replacing the waker registered by an earlier poll.
+    fn schedule(&self, deadline: Instant, id: u64, waker: &Waker) {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        match deadlines.get(&(deadline, id)) {
+            Some(existing) if existing.will_wake(waker) => {},
+            _ => {
+                deadlines.insert((deadline, id), waker.clone());
+                self.condvar.notify_one();
+            }
+        }
+    }
+
+    /// Forgets the deadline of the future `id`.
+    fn cancel(deadline: Instant, id: u64) {
+        if let Some(timer) = DEADLINE_TIMER.get() {
+            timer.deadlines.lock().unwrap().remove(&(deadline, id));
+        }
+    }
+
+    fn run(&self) {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let pending = deadlines.split_off(&(now, u64::MAX));
+            let expired = std::mem::replace(&mut *deadlines, pending);
+            if !expired.is_empty() {
+                drop(deadlines);
+                for waker in expired.into_values() {
+                    waker.wake();
+                }
+                deadlines = self.deadlines.lock().unwrap();
+                continue;
+            }
+            deadlines = match deadlines.keys().next() {
+                Some(&(deadline, _)) => self.condvar.wait_timeout(deadlines, deadline - now).unwrap().0,
+                None => self.condvar.wait(deadlines).unwrap()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_transform;
+    use std::task::Wake;
+    use std::thread::Thread;
+    use std::time::Duration;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor which parks the current thread until the future is woken.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(res) => return res,
+                Poll::Pending => thread::park()
+            }
+        }
+    }
+
+    /// Tests that the future resolves once the transform is added to the buffer.
+    #[test]
+    fn test_resolves_on_insertion() {
+        let buffer = Arc::new(RwLock::new(TfBuffer::new()));
+        let future = TransformFuture::new(buffer.clone(), "world", "base_link", rosrust::Time{sec: 1, nsec: 0}, 
+            Instant::now() + Duration::from_secs(10));
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            buffer.write().unwrap().set_transform(&test_transform("world", "base_link", 1f64, [1f64, 0f64, 0f64]), "test", true).unwrap();
+        });
+        let res = block_on(future).unwrap();
+        assert_eq!(res.transform.translation.x, 1f64);
+        writer.join().unwrap();
+    }
+
+    /// Tests that the future fails once the deadline has passed.
+    #[test]
+    fn test_fails_at_deadline() {
+        let buffer = Arc::new(RwLock::new(TfBuffer::new()));
+        let start = Instant::now();
+        let future = TransformFuture::new(buffer, "world", "base_link", rosrust::Time{sec: 1, nsec: 0}, 
+            start + Duration::from_millis(50));
+        assert!(block_on(future).is_err());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+    /// Tests that dropping a pending future cancels its deadline.
+    #[test]
+    fn test_drop_cancels_deadline() {
+        let buffer = Arc::new(RwLock::new(TfBuffer::new()));
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut future = Box::pin(TransformFuture::new(buffer, "world", "base_link", rosrust::Time{sec: 1, nsec: 0}, deadline));
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+        assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+
+        let key = (deadline, future.id);
+        let timer = DEADLINE_TIMER.get().unwrap();
+        assert!(timer.deadlines.lock().unwrap().contains_key(&key));
+        drop(future);
+        assert!(!timer.deadlines.lock().unwrap().contains_key(&key));
+    }
+
+    /// Tests that a pending future registers one waker, which is removed when it is dropped.
+    #[test]
+    fn test_drop_unregisters_waker() {
+        let buffer = Arc::new(RwLock::new(TfBuffer::new()));
+        let mut future = Box::pin(TransformFuture::new(buffer.clone(), "world", "base_link", rosrust::Time{sec: 1, nsec: 0}, 
+            Instant::now() + Duration::from_secs(60)));
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+        assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+        assert_eq!(buffer.read().unwrap().wakers().len(), 1);
+        assert_eq!(buffer.read().unwrap().clone().wakers().len(), 0);
+        drop(future);
+        assert_eq!(buffer.read().unwrap().wakers().len(), 0);
+    }
+}
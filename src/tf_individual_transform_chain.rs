@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::{msg, get_nanos, to_transform, transforms};
+use crate::tf_error::TfError;
+use crate::tf_extrapolation::ExtrapolationPolicy;
+use crate::tf_interpolation::InterpolationMode;
+use crate::tf_provenance::TransformHop;
+use crate::tf_edge_info::TfEdgeInfo;
+
+/// Settings which control a lookup on a single edge.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EdgeLookupOptions {
+    pub extrapolation: ExtrapolationPolicy,
+    pub interpolation: InterpolationMode,
+    /// Largest allowed time between the two transforms a lookup is interpolated from
+    pub max_gap: Option<rosrust::Duration>
+}
+
+/// Which samples a lookup on a single edge used. Cheap to return from every lookup, the `TransformHop` describing
+/// it is only built for lookups which ask for it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UsedSamples {
+    before: usize,
+    after: usize,
+    weight: f64,
+    /// Policy which was applied because the requested time was outside of the data available on the edge
+    pub extrapolation: ExtrapolationPolicy
+}
+
+/// Time ordered cache of the transforms along a single edge of the frame graph.
+///
+/// Transforms normally arrive in order, in which case they are appended to the back of a ring buffer and 
+/// expired transforms are popped off the front, both in amortised O(1). Transforms arriving out of order 
+/// fall back to an O(n) insertion at the correct position.
+#[derive(Clone, Debug)] 
+pub(crate) struct TfIndividualTransformChain {
+    /// How far back in time transforms are kept, relative to the latest transform on this edge
+    cache_duration: rosrust::Duration,
+    static_tf: bool,
+    /// Caller id of the node which last published on this edge
+    authority: String,
+    transform_chain: VecDeque<msg::geometry_msgs::TransformStamped>,
+    /// Caller id of the node which published each transform in `transform_chain`, shared between consecutive 
+    /// transforms from the same node
+    authorities: VecDeque<Arc<str>>,
+    /// When each transform in `transform_chain` was received, if it was received from ROS
+    receive_times: VecDeque<Option<rosrust::Time>>
+}
+
+
+impl TfIndividualTransformChain {
+    pub fn new(static_tf: bool, cache_duration: rosrust::Duration) -> Self {
+        TfIndividualTransformChain{cache_duration, transform_chain: VecDeque::new(), authorities: VecDeque::new(), receive_times: VecDeque::new(), static_tf, authority: String::new()}
+    }
+
+    pub fn add_to_buffer(&mut self, msg: msg::geometry_msgs::TransformStamped, authority: &str, receive_time: Option<rosrust::Time>) {
+        let in_order = match self.transform_chain.back() {
+            Some(latest) => msg.header.stamp >= latest.header.stamp,
+            None => true
+        };
+        let source = match self.authorities.back() {
+            Some(last) if &**last == authority => last.clone(),
+            _ => Arc::from(authority)
+        };
+
+        if in_order {
+            self.transform_chain.push_back(msg);
+            self.authorities.push_back(source);
+            self.receive_times.push_back(receive_time);
+        }
+        else {
+            let index = match self.transform_chain.binary_search_by(|probe| probe.header.stamp.cmp(&msg.header.stamp)) {
+                Ok(x) => x,
+                Err(x) => x
+            };
+            self.transform_chain.insert(index, msg);
+            self.authorities.insert(index, source);
+            self.receive_times.insert(index, receive_time);
+        }
+
+        if self.static_tf {
+            // Static transforms are valid for all time, so only the latest one is of any use.
+            while self.transform_chain.len() > 1 {
+                self.transform_chain.pop_front();
+                self.authorities.pop_front();
+                self.receive_times.pop_front();
+            }
+        }
+        else {
+            let latest = self.transform_chain.back().unwrap().header.stamp;
+            let cache_nanos = get_nanos(self.cache_duration);
+            while get_nanos(latest - self.transform_chain.front().unwrap().header.stamp) > cache_nanos {
+                self.transform_chain.pop_front();
+                self.authorities.pop_front();
+                self.receive_times.pop_front();
+            }
+        }
+
+        if self.authority != authority {
+            self.authority = authority.to_string();
+        }
+    }
+
+    /// Caller id of the node which last published on this edge
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+
+    /// Time of the newest transform on this edge. Static edges are valid at all times so they have none.
+    pub fn latest_time(&self) -> Option<rosrust::Time> {
+        if self.static_tf {
+            return None;
+        }
+        self.transform_chain.back().map(|x| x.header.stamp)
+    }
+
+    /// Statistics about the transforms stored on this edge.
+    pub fn edge_info(&self) -> TfEdgeInfo {
+        let oldest = self.transform_chain.front().unwrap();
+        let latest = self.transform_chain.back().unwrap();
+        let (average_delay, max_delay) = self.delays();
+        TfEdgeInfo {
+            parent: latest.header.frame_id.clone(),
+            child: latest.child_frame_id.clone(),
+            authority: self.authority.clone(),
+            static_tf: self.static_tf,
+            samples: self.transform_chain.len(),
+            oldest: oldest.header.stamp,
+            latest: latest.header.stamp,
+            average_delay,
+            max_delay
+        }
+    }
+
+    /// Average and largest time between the stamp of a transform and when it was received, over the transforms 
+    /// with a known receive time. Static transforms are valid for all time so they are not delayed.
+    fn delays(&self) -> (Option<rosrust::Duration>, Option<rosrust::Duration>) {
+        if self.static_tf {
+            return (None, None);
+        }
+        let delays: Vec<i64> = self.transform_chain.iter().zip(self.receive_times.iter())
+            .filter_map(|(tf, received)| received.map(|received| get_nanos(received - tf.header.stamp)))
+            .collect();
+        if delays.is_empty() {
+            return (None, None);
+        }
+        let average = delays.iter().sum::<i64>() / delays.len() as i64;
+        let max = *delays.iter().max().unwrap();
+        (Some(rosrust::Duration::from_nanos(average)), Some(rosrust::Duration::from_nanos(max)))
+    }
+
+    /// The transforms stored on this edge, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = &msg::geometry_msgs::TransformStamped> {
+        self.transform_chain.iter()
+    }
+
+    /// Checks whether `get_closest_transform` would succeed, without interpolating.
+    pub fn can_transform(&self, time: rosrust::Time, options: &EdgeLookupOptions) -> Result<(), TfError> {
+        if self.static_tf {
+            return Ok(());
+        }
+        match self.transform_chain.binary_search_by(|probe| probe.header.stamp.cmp(&time)) {
+            Ok(_) => Ok(()),
+            Err(x) => match self.check_in_range(x, time) {
+                Ok(()) => self.check_gap(x, time, options.max_gap),
+                Err(_) if self.can_extrapolate(x, time, options.extrapolation) => Ok(()),
+                res => res
+            }
+        }
+    }
+
+    /// Checks that the transforms on either side of a lookup at `time`, which would be inserted at `index`, are 
+    /// close enough together to be interpolated.
+    fn check_gap(&self, index: usize, time: rosrust::Time, max_gap: Option<rosrust::Duration>) -> Result<(), TfError> {
+        let max_gap = match max_gap {
+            Some(max_gap) => max_gap,
+            None => return Ok(())
+        };
+        let before = &self.transform_chain[index - 1];
+        let after = &self.transform_chain[index];
+        let gap = after.header.stamp - before.header.stamp;
+        if get_nanos(gap) > get_nanos(max_gap) {
+            return Err(TfError::InterpolationGapExceeded{
+                parent: after.header.frame_id.clone(),
+                child: after.child_frame_id.clone(),
+                time,
+                gap,
+                max_gap
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks whether the policy allows extrapolating to `time`, which would be inserted at `index`.
+    fn can_extrapolate(&self, index: usize, time: rosrust::Time, policy: ExtrapolationPolicy) -> bool {
+        let max = match policy.max_duration() {
+            Some(max) => get_nanos(max),
+            None => return false
+        };
+        let nearest = if index == 0 { self.transform_chain.front() } else { self.transform_chain.back() };
+        get_nanos(time - nearest.unwrap().header.stamp).abs() <= max
+    }
+
+    /// Extrapolates the transforms on this edge to `time` which would be inserted at `index`. 
+    fn extrapolate(&self, index: usize, time: rosrust::Time, policy: ExtrapolationPolicy) -> (transforms::Transform, UsedSamples) {
+        let len = self.transform_chain.len();
+        let nearest_index = if index == 0 { 0 } else { len - 1 };
+        let nearest = &self.transform_chain[nearest_index];
+        // Samples stamped the same as the nearest one give no velocity, so use the closest with a different stamp
+        let other = if index == 0 {
+            self.transform_chain.iter().position(|x| x.header.stamp != nearest.header.stamp)
+        } else {
+            self.transform_chain.iter().rposition(|x| x.header.stamp != nearest.header.stamp)
+        };
+        match (policy, other) {
+            (ExtrapolationPolicy::ConstantVelocity(_), Some(other)) => {
+                // Use the two transforms closest to the requested time to estimate the velocity
+                let (index1, index2) = if index == 0 { (nearest_index, other) } else { (other, nearest_index) };
+                let (tf1, tf2) = (&self.transform_chain[index1], &self.transform_chain[index2]);
+                let total_duration = get_nanos(tf2.header.stamp - tf1.header.stamp) as f64;
+                let desired_duration = get_nanos(time - tf1.header.stamp) as f64;
+                let weight = desired_duration/total_duration;
+                (transforms::extrapolate(to_transform(tf1), to_transform(tf2), weight), 
+                    UsedSamples{before: index1, after: index2, weight, extrapolation: policy})
+            },
+            _ => {
+                // Without a velocity, constant velocity extrapolation falls back to holding the nearest transform
+                let applied = match policy {
+                    ExtrapolationPolicy::ConstantVelocity(max) => ExtrapolationPolicy::Bounded(max),
+                    _ => policy
+                };
+                (to_transform(nearest), UsedSamples{before: nearest_index, after: nearest_index, weight: 0.0, extrapolation: applied})
+            }
+        }
+    }
+
+    /// Describes a lookup on this edge which used `samples`.
+    pub fn hop(&self, samples: &UsedSamples) -> TransformHop {
+        let sample = &self.transform_chain[samples.after];
+        TransformHop {
+            parent: sample.header.frame_id.clone(),
+            child: sample.child_frame_id.clone(),
+            inverted: false,
+            before: self.transform_chain[samples.before].header.stamp,
+            after: sample.header.stamp,
+            weight: samples.weight,
+            before_authority: self.authorities[samples.before].to_string(),
+            after_authority: self.authorities[samples.after].to_string(),
+            static_tf: self.static_tf,
+            extrapolation: samples.extrapolation
+        }
+    }
+
+    /// Checks that a lookup at `time`, which would be inserted at `index`, is bracketed by two transforms.
+    fn check_in_range(&self, index: usize, time: rosrust::Time) -> Result<(), TfError> {
+        let earliest = self.transform_chain.front().unwrap();
+        let latest = self.transform_chain.back().unwrap();
+        if index == 0 {
+            return Err(TfError::AttemptedLookupInPast{
+                parent: latest.header.frame_id.clone(),
+                child: latest.child_frame_id.clone(),
+                time,
+                earliest: earliest.header.stamp,
+                latest: latest.header.stamp
+            });
+        }
+        if index >= self.transform_chain.len() {
+            return Err(TfError::AttemptedLookUpInFuture{
+                parent: latest.header.frame_id.clone(),
+                child: latest.child_frame_id.clone(),
+                time,
+                earliest: earliest.header.stamp,
+                latest: latest.header.stamp
+            });
+        }
+        Ok(())
+    }
+
+    /// Gets the transform on this edge at `time`, interpolating between the transforms on either side of it. If `time` 
+    /// is outside of the available data, the policy decides whether to extrapolate. Also returns which samples were
+    /// used.
+    pub fn get_closest_transform(&self, time: rosrust::Time, options: &EdgeLookupOptions) -> Result<(transforms::Transform, UsedSamples), TfError> {
+        let exact = |x: usize| (to_transform(&self.transform_chain[x]), UsedSamples{before: x, after: x, weight: 0.0, extrapolation: ExtrapolationPolicy::Strict});
+        if self.static_tf {
+            return Ok(exact(self.transform_chain.len() - 1));
+        }
+
+        let res = self.transform_chain.binary_search_by(|probe| probe.header.stamp.cmp(&time));
+        match res {
+            Ok(x)=> Ok(exact(x)),
+            Err(x)=> {
+                if let Err(e) = self.check_in_range(x, time) {
+                    if self.can_extrapolate(x, time, options.extrapolation) {
+                        return Ok(self.extrapolate(x, time, options.extrapolation));
+                    }
+                    return Err(e);
+                }
+                self.check_gap(x, time, options.max_gap)?;
+                let tf1 = to_transform(&self.transform_chain[x - 1]);
+                let tf2 = to_transform(&self.transform_chain[x]);
+                let time1 = self.transform_chain[x - 1].header.stamp;
+                let time2 = self.transform_chain[x].header.stamp;
+                let total_duration = get_nanos(time2 - time1) as f64;
+                let desired_duration = get_nanos(time - time1) as f64;
+                let fraction = desired_duration/total_duration;
+                let (final_tf, weight) = match options.interpolation {
+                    InterpolationMode::Nearest => if fraction < 0.5 { (tf1, 0.0) } else { (tf2, 1.0) },
+                    InterpolationMode::Hold => (tf1, 0.0),
+                    InterpolationMode::Linear => (transforms::interpolate(tf1, tf2, fraction), fraction),
+                    InterpolationMode::CubicSpline => (transforms::cubic_interpolate(&self.spline_samples(x, time1), desired_duration * 1e-9), fraction)
+                };
+                Ok((final_tf, UsedSamples{before: x - 1, after: x, weight, extrapolation: ExtrapolationPolicy::Strict}))
+            }
+        }
+    }
+
+    /// Gets the four samples around a lookup which would be inserted at `index`, with times in seconds relative 
+    /// to `reference`. Missing neighbours at the ends of the data are replaced by the closest sample.
+    fn spline_samples(&self, index: usize, reference: rosrust::Time) -> [(f64, transforms::Transform); 4] {
+        let last = self.transform_chain.len() - 1;
+        let sample = |i: usize| {
+            let tf = &self.transform_chain[i];
+            (get_nanos(tf.header.stamp - reference) as f64 * 1e-9, to_transform(tf))
+        };
+        [
+            sample(if index >= 2 { index - 2 } else { index - 1 }),
+            sample(index - 1),
+            sample(index),
+            sample(if index < last { index + 1 } else { index })
+        ]
+    }
+}
@@ -0,0 +1,14 @@
+/// Controls how the transform on an edge is computed between two of its samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InterpolationMode {
+    /// Use whichever of the two samples is closest in time.
+    Nearest,
+    /// Zero order hold: use the sample before the requested time. Useful for frames which jump between discrete 
+    /// states such as a gripper.
+    Hold,
+    /// Linear interpolation of the translation and spherical linear interpolation of the rotation. This is the default.
+    #[default]
+    Linear,
+    /// Cubic spline through the two samples and their neighbours, giving smooth velocities across samples.
+    CubicSpline
+}
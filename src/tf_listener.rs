@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::{msg, get_nanos};
+use crate::tf_buffer::{TfBuffer, DEFAULT_CACHE_DURATION_SECONDS};
+use crate::tf_error::TfError;
+use crate::tf_extrapolation::{ExtrapolatedTransform, ExtrapolationPolicy};
+use crate::tf_interpolation::InterpolationMode;
+use crate::tf_provenance::DetailedTransform;
+use crate::tf_edge_info::TfEdgeInfo;
+use crate::tf_future::TransformFuture;
+use crate::tf_bag::BagWriter;
+
+/// Bag which the subscribers write incoming transforms to while recording.
+type BagRecorder = Arc<Mutex<Option<BagWriter<BufWriter<File>>>>>;
+
+///This struct tries to be the same as the C++ version of `TransformListener`. Use this struct to lookup transforms.
+/// 
+/// Example usage:
+/// 
+/// ```ignore
+/// fn main() {
+///     rosrust::init("listener");
+///     let listener = TfListener::new();
+///     
+///     let rate = rosrust::rate(1.0);
+///     while rosrust::is_ok() {
+///         let tf = listener.lookup_transform("camera", "base_link", ros::Time::now());
+///         println!("{:?}", tf);
+///         rate.sleep();
+///     }
+/// }
+/// ```
+/// Do note that unlike the C++ variant of the TfListener, only one TfListener can be created at a time. Like its C++ counterpart,
+/// it must be scoped to exist through the lifetime of the program. One way to do this is using an `Arc` or `RwLock`.
+pub struct TfListener {
+    buffer: Arc<RwLock<TfBuffer>>,
+    notifier: Arc<TfUpdateNotifier>,
+    recorder: BagRecorder,
+    static_subscriber: rosrust::Subscriber,
+    dynamic_subscriber:  rosrust::Subscriber, 
+}
+
+impl TfListener {
+
+    /// Create a new TfListener which keeps `DEFAULT_CACHE_DURATION_SECONDS` of history for every edge.
+    pub fn new() -> Self {
+        TfListener::new_with_duration(rosrust::Duration{sec: DEFAULT_CACHE_DURATION_SECONDS, nsec: 0})
+    }
+
+    /// Create a new TfListener which keeps `cache_duration` of history for every edge.
+    pub fn new_with_duration(cache_duration: rosrust::Duration) -> Self {
+        let buff = RwLock::new(TfBuffer::new_with_duration(cache_duration));
+        let arc = Arc::new(buff);
+        let notifier = Arc::new(TfUpdateNotifier::new());
+        let recorder: BagRecorder = Arc::new(Mutex::new(None));
+        let r1 = arc.clone();
+        let n1 = notifier.clone();
+        let b1 = recorder.clone();
+        let _subscriber_tf = rosrust::subscribe_with_ids("tf", 100, move |v: msg::tf2_msgs::TFMessage, caller_id: &str| {
+            record(&b1, &v, false, caller_id);
+            let errors = r1.write().unwrap().handle_incoming_transforms(v, false, caller_id, rosrust::now());
+            for e in errors {
+                rosrust::ros_warn!("{}", e);
+            }
+            n1.notify();
+        }).unwrap();
+
+        let r2 = arc.clone();
+        let n2 = notifier.clone();
+        let b2 = recorder.clone();
+        let _subscriber_tf_static = rosrust::subscribe_with_ids("tf_static", 100, move |v: msg::tf2_msgs::TFMessage, caller_id: &str| {
+            record(&b2, &v, true, caller_id);
+            let errors = r2.write().unwrap().handle_incoming_transforms(v, true, caller_id, rosrust::now());
+            for e in errors {
+                rosrust::ros_warn!("{}", e);
+            }
+            n2.notify();
+        }).unwrap();
+        
+        TfListener {
+            buffer: arc.clone(),
+            notifier,
+            recorder,
+            static_subscriber: _subscriber_tf_static,
+            dynamic_subscriber: _subscriber_tf
+        }
+    }
+
+    /// Looks up a transform within the tree at a given time. A time of zero looks up the transform at the latest
+    /// time at which it is available.
+    pub fn lookup_transform(&self, from: &str, to: &str, time: rosrust::Time) ->  Result<msg::geometry_msgs::TransformStamped,TfError> {
+        self.buffer.read().unwrap().lookup_transform(from, to, time)
+    }
+
+    /// Looks up a transform within the tree at a given time, using `policy` instead of the listener's extrapolation
+    /// policy. The result reports which policy had to be applied.
+    pub fn lookup_transform_with_policy(&self, from: &str, to: &str, time: rosrust::Time, policy: ExtrapolationPolicy) -> Result<ExtrapolatedTransform,TfError> {
+        self.buffer.read().unwrap().lookup_transform_with_policy(from, to, time, policy)
+    }
+
+    /// Sets the policy used for lookups outside of the range of data available on an edge. By default lookups 
+    /// outside of the available data fail.
+    pub fn set_extrapolation_policy(&self, policy: ExtrapolationPolicy) {
+        self.buffer.write().unwrap().set_extrapolation_policy(policy);
+    }
+
+    /// Looks up a transform within the tree at a given time, interpolating every edge along the path using `mode`
+    /// instead of the edge's own interpolation mode.
+    pub fn lookup_transform_with_interpolation(&self, from: &str, to: &str, time: rosrust::Time, mode: InterpolationMode) -> Result<msg::geometry_msgs::TransformStamped,TfError> {
+        self.buffer.read().unwrap().lookup_transform_with_interpolation(from, to, time, mode)
+    }
+
+    /// Sets how transforms on the edge from `parent` to `child` are interpolated between samples.
+    pub fn set_interpolation_mode(&self, parent: &str, child: &str, mode: InterpolationMode) {
+        self.buffer.write().unwrap().set_interpolation_mode(parent, child, mode);
+    }
+
+    /// Sets the largest time between two samples that lookups may interpolate across. `None` allows gaps of any size.
+    pub fn set_max_interpolation_gap(&self, max_gap: Option<rosrust::Duration>) {
+        self.buffer.write().unwrap().set_max_interpolation_gap(max_gap);
+    }
+
+    /// Overrides the largest time between two samples that lookups may interpolate across for the edge from `parent` to `child`.
+    pub fn set_edge_max_interpolation_gap(&self, parent: &str, child: &str, max_gap: Option<rosrust::Duration>) {
+        self.buffer.write().unwrap().set_edge_max_interpolation_gap(parent, child, max_gap);
+    }
+
+    /// Looks up a transform within the tree at a given time, also returning which samples, interpolation weights 
+    /// and authorities were used for every edge along the path.
+    pub fn lookup_transform_detailed(&self, from: &str, to: &str, time: rosrust::Time) -> Result<DetailedTransform,TfError> {
+        self.buffer.read().unwrap().lookup_transform_detailed(from, to, time)
+    }
+
+    /// Looks up a transform within the tree at a given time.
+    pub fn lookup_transform_with_time_travel(&self, from: &str, time1: rosrust::Time, to: &str, time2: rosrust::Time, fixed_frame: &str) ->  Result<msg::geometry_msgs::TransformStamped,TfError> {
+        self.buffer.read().unwrap().lookup_transform_with_time_travel(from, time1, to, time2, fixed_frame)
+    }
+
+    /// Gets the latest time at which a transform between the two frames is available.
+    pub fn get_latest_common_time(&self, from: &str, to: &str) -> Result<rosrust::Time, TfError> {
+        self.buffer.read().unwrap().get_latest_common_time(from, to)
+    }
+
+    /// Checks whether a transform between the two frames can be looked up at the given time, without composing
+    /// the transforms along the path. On failure the error explains why the transform is not available.
+    pub fn can_transform(&self, from: &str, to: &str, time: rosrust::Time) -> Result<(), TfError> {
+        self.buffer.read().unwrap().can_transform(from, to, time)
+    }
+
+    /// Looks up a transform within the tree at a given time, blocking until the transform becomes available. 
+    /// Gives up after `timeout` or when ROS shuts down, returning the error of the last attempt.
+    pub fn lookup_transform_with_timeout(&self, from: &str, to: &str, time: rosrust::Time, timeout: rosrust::Duration) ->  Result<msg::geometry_msgs::TransformStamped,TfError> {
+        self.wait_for(timeout, || self.lookup_transform(from, to, time))
+    }
+
+    /// Blocks until a transform between the two frames can be looked up at the given time. 
+    /// Gives up after `timeout` or when ROS shuts down, returning the reason the transform is unavailable.
+    pub fn wait_for_transform(&self, from: &str, to: &str, time: rosrust::Time, timeout: rosrust::Duration) -> Result<(), TfError> {
+        self.wait_for(timeout, || self.can_transform(from, to, time))
+    }
+
+    /// Looks up a transform within the tree at a given time without blocking. The returned future resolves once the 
+    /// transform becomes available, or to the error of the last attempt once `timeout` has passed.
+    pub fn lookup_transform_async(&self, from: &str, to: &str, time: rosrust::Time, timeout: rosrust::Duration) -> TransformFuture {
+        let deadline = Instant::now() + Duration::from_nanos(get_nanos(timeout).max(0) as u64);
+        TransformFuture::new(self.buffer.clone(), from, to, time, deadline)
+    }
+
+    /// Checks whether the frame is part of the tree, either as the parent or as the child of an edge.
+    pub fn frame_exists(&self, frame: &str) -> bool {
+        self.buffer.read().unwrap().frame_exists(frame)
+    }
+
+    /// Gets the parent of a frame. Roots of the tree and unknown frames have none.
+    pub fn get_parent(&self, frame: &str) -> Option<String> {
+        self.buffer.read().unwrap().get_parent(frame)
+    }
+
+    /// Gets the children of a frame, sorted by name.
+    pub fn get_children(&self, frame: &str) -> Vec<String> {
+        self.buffer.read().unwrap().get_children(frame)
+    }
+
+    /// Gets every frame in the tree, sorted by name.
+    pub fn all_frames(&self) -> Vec<String> {
+        self.buffer.read().unwrap().all_frames()
+    }
+
+    /// Gets the frames a lookup from `from` to `to` passes through, not including `from`.
+    pub fn chain(&self, from: &str, to: &str) -> Result<Vec<String>, TfError> {
+        self.buffer.read().unwrap().chain(from, to)
+    }
+
+    /// Gets the stamps of the oldest and newest transforms stored on the edge from `parent` to `child`.
+    pub fn edge_time_range(&self, parent: &str, child: &str) -> Option<(rosrust::Time, rosrust::Time)> {
+        self.buffer.read().unwrap().edge_time_range(parent, child)
+    }
+
+    /// Statistics about every edge of the tree, sorted by parent and then by child frame.
+    pub fn edges(&self) -> Vec<TfEdgeInfo> {
+        self.buffer.read().unwrap().edges()
+    }
+
+    /// Exports the tree in the Graphviz DOT format, like tf2's `view_frames`.
+    pub fn all_frames_as_dot(&self) -> String {
+        self.buffer.read().unwrap().all_frames_as_dot()
+    }
+
+    /// Describes every frame which has a parent in the same YAML format as tf2's `allFramesAsYAML`.
+    pub fn all_frames_as_yaml(&self) -> String {
+        self.buffer.read().unwrap().all_frames_as_yaml()
+    }
+
+    /// Describes every frame which has a parent in the same YAML format as tf2's `allFramesAsYAML`, including how
+    /// old the most recent transform of each frame is at `current_time`.
+    pub fn all_frames_as_yaml_at(&self, current_time: rosrust::Time) -> String {
+        self.buffer.read().unwrap().all_frames_as_yaml_at(current_time)
+    }
+
+    /// Lists every frame which has a parent in the same format as tf2's `allFramesAsString`.
+    pub fn all_frames_as_string(&self) -> String {
+        self.buffer.read().unwrap().all_frames_as_string()
+    }
+
+    /// Writes every transform received so far, so that it can be restored later using `TfBuffer::load_snapshot`.
+    pub fn save_snapshot<W: io::Write>(&self, w: W) -> io::Result<()> {
+        self.buffer.read().unwrap().save_snapshot(w)
+    }
+
+    /// Starts recording every transform the listener receives to a rosbag v1 file at `path`, which can be read with
+    /// `read_bag` or played back with `rosbag play`. Any earlier recording is finished first.
+    pub fn start_recording<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let writer = BagWriter::create(path)?;
+        let previous = self.recorder.lock().unwrap().replace(writer);
+        match previous {
+            Some(mut previous) => previous.finish(),
+            None => Ok(())
+        }
+    }
+
+    /// Stops recording and completes the bag. Does nothing if the listener is not recording.
+    pub fn stop_recording(&self) -> io::Result<()> {
+        let writer = self.recorder.lock().unwrap().take();
+        match writer {
+            Some(mut writer) => writer.finish(),
+            None => Ok(())
+        }
+    }
+
+    /// Retries `attempt` every time new transforms arrive until it succeeds or the timeout expires.
+    fn wait_for<T, F>(&self, timeout: rosrust::Duration, attempt: F) -> Result<T, TfError> 
+        where F: Fn() -> Result<T, TfError> {
+        let deadline = Instant::now() + Duration::from_nanos(get_nanos(timeout).max(0) as u64);
+        loop {
+            let updates = self.notifier.updates();
+            let res = attempt();
+            let now = Instant::now();
+            if res.is_ok() || now >= deadline || !rosrust::is_ok() {
+                return res;
+            }
+            // Wake up periodically even without new transforms to notice ROS shutting down.
+            self.notifier.wait(updates, (deadline - now).min(SHUTDOWN_POLL_INTERVAL));
+        }
+    }
+}
+
+/// Writes a message received by a subscriber to the bag, if the listener is recording.
+fn record(recorder: &BagRecorder, msg: &msg::tf2_msgs::TFMessage, static_tf: bool, caller_id: &str) {
+    if let Some(ref mut writer) = *recorder.lock().unwrap() {
+        if let Err(e) = writer.write(msg, static_tf, caller_id, rosrust::now()) {
+            rosrust::ros_warn!("Could not record transforms: {}", e);
+        }
+    }
+}
+
+/// How often a blocking lookup checks whether ROS is shutting down.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Counts the messages received by the subscribers, waking up blocked lookups whenever a message arrives.
+struct TfUpdateNotifier {
+    updates: Mutex<u64>,
+    condvar: Condvar
+}
+
+impl TfUpdateNotifier {
+    fn new() -> Self {
+        TfUpdateNotifier{updates: Mutex::new(0), condvar: Condvar::new()}
+    }
+
+    fn updates(&self) -> u64 {
+        *self.updates.lock().unwrap()
+    }
+
+    fn notify(&self) {
+        *self.updates.lock().unwrap() += 1;
+        self.condvar.notify_all();
+    }
+
+    /// Waits until a message has arrived since `updates` was read, or until `timeout` expires.
+    fn wait(&self, updates: u64, timeout: Duration) {
+        let guard = self.updates.lock().unwrap();
+        let _ = self.condvar.wait_timeout_while(guard, timeout, |current| *current == updates).unwrap();
+    }
+}
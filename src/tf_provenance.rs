@@ -0,0 +1,37 @@
+use crate::msg;
+use crate::tf_extrapolation::ExtrapolationPolicy;
+
+/// Describes how the transform across one edge of a lookup path was computed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformHop {
+    /// Parent frame of the edge
+    pub parent: String,
+    /// Child frame of the edge
+    pub child: String,
+    /// Set if the path went from the child to the parent, in which case the stored transform was inverted
+    pub inverted: bool,
+    /// Stamp of the sample at or before the requested time. For static edges this is the stamp of the only sample.
+    pub before: rosrust::Time,
+    /// Stamp of the sample at or after the requested time. For static edges this is the stamp of the only sample.
+    pub after: rosrust::Time,
+    /// Fraction of the way from the `before` sample to the `after` sample which was used. This lies outside of 
+    /// `[0, 1]` when extrapolating at a constant velocity.
+    pub weight: f64,
+    /// Caller id of the node which published the `before` sample
+    pub before_authority: String,
+    /// Caller id of the node which published the `after` sample
+    pub after_authority: String,
+    /// Set if the edge holds static transforms
+    pub static_tf: bool,
+    /// Policy which was applied because the requested time was outside of the data available on the edge. This is
+    /// `ExtrapolationPolicy::Strict` if the edge was not extrapolated.
+    pub extrapolation: ExtrapolationPolicy
+}
+
+/// Result of a detailed lookup: the composed transform and how each edge along the path contributed to it.
+#[derive(Clone, Debug)]
+pub struct DetailedTransform {
+    pub transform: msg::geometry_msgs::TransformStamped,
+    /// One entry per edge, in the order the path goes from the source frame to the target frame
+    pub hops: Vec<TransformHop>
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use crate::{msg, to_transform_stamped, transforms};
+use crate::tf_broadcaster::sequenced_message;
+
+/// Publishes transforms which do not change over time on `/tf_static`, like the C++ `StaticTransformBroadcaster`.
+///
+/// The topic is latched and a latched topic only keeps the last message, so every send republishes all of the
+/// static transforms sent so far by this broadcaster. Sending a transform for a child frame which was sent
+/// before replaces the earlier transform.
+pub struct StaticTfBroadcaster {
+    publisher: rosrust::Publisher<msg::tf2_msgs::TFMessage>,
+    /// Every transform sent so far, keyed by child frame
+    transforms: HashMap<String, msg::geometry_msgs::TransformStamped>,
+    next_seq: u32
+}
+
+impl StaticTfBroadcaster {
+
+    /// Create a new StaticTfBroadcaster publishing latched on `/tf_static`.
+    pub fn new() -> Self {
+        let mut publisher = rosrust::publish("tf_static", 100).unwrap();
+        publisher.set_latching(true);
+        StaticTfBroadcaster{publisher, transforms: HashMap::new(), next_seq: 0}
+    }
+
+    /// Adds a single transform and republishes all static transforms.
+    pub fn send_transform(&mut self, transform: msg::geometry_msgs::TransformStamped) -> Result<(), rosrust::error::Error> {
+        self.send_transforms(vec![transform])
+    }
+
+    /// Adds several transforms and republishes all static transforms.
+    pub fn send_transforms(&mut self, transforms: Vec<msg::geometry_msgs::TransformStamped>) -> Result<(), rosrust::error::Error> {
+        let all = merge_transforms(&mut self.transforms, transforms);
+        let msg = sequenced_message(&mut self.next_seq, all);
+        self.publisher.send(msg)
+    }
+
+    /// Adds the transform from `parent` to `child` at `time` and republishes all static transforms.
+    pub fn send_transform_with_frames(&mut self, transform: &transforms::Transform, parent: &str, child: &str, time: rosrust::Time) -> Result<(), rosrust::error::Error> {
+        self.send_transform(to_transform_stamped(*transform, parent.to_string(), child.to_string(), time))
+    }
+}
+
+impl Default for StaticTfBroadcaster {
+    fn default() -> Self {
+        StaticTfBroadcaster::new()
+    }
+}
+
+/// Adds the transforms to those sent so far, replacing any with the same child frame, and returns the full set
+/// ordered by child frame.
+fn merge_transforms(sent: &mut HashMap<String, msg::geometry_msgs::TransformStamped>,
+    transforms: Vec<msg::geometry_msgs::TransformStamped>) -> Vec<msg::geometry_msgs::TransformStamped> {
+    for transform in transforms {
+        sent.insert(transform.child_frame_id.clone(), transform);
+    }
+    let mut all: Vec<_> = sent.values().cloned().collect();
+    all.sort_by(|a, b| a.child_frame_id.cmp(&b.child_frame_id));
+    all
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_transform;
+
+    /// Tests that later sends keep the earlier transforms and replace those of the same child frame.
+    #[test]
+    fn test_merge_transforms() {
+        let mut sent = HashMap::new();
+        let all = merge_transforms(&mut sent, vec![test_transform("world", "b", 0f64, [1f64, 0f64, 0f64])]);
+        assert_eq!(all.len(), 1);
+
+        let all = merge_transforms(&mut sent, vec![test_transform("world", "a", 0f64, [2f64, 0f64, 0f64]), test_transform("b", "c", 0f64, [3f64, 0f64, 0f64])]);
+        let children: Vec<&str> = all.iter().map(|x| x.child_frame_id.as_str()).collect();
+        assert_eq!(children, vec!["a", "b", "c"]);
+
+        let all = merge_transforms(&mut sent, vec![test_transform("a", "b", 0f64, [4f64, 0f64, 0f64])]);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[1].header.frame_id, "a");
+        assert_eq!(all[1].transform.translation.x, 4f64);
+    }
+}
@@ -28,23 +28,24 @@ pub struct Transform {
     pub position: Position
 }
 
-/// Converts a SE3 transformation matrix to a quaternion
+/// Converts a SE3 transformation matrix to a quaternion. The matrix holds the transpose of the rotation, see 
+/// `transform_matrix_from_quaternion`.
 pub fn quaternion_from_transform_matrix(a: &Array2<f64>) ->  Quaternion {
     let tr = a.trace().unwrap();
     let trace = tr - 1f64;
     if trace > 0f64 {
         let qw = (1f64 + trace).sqrt()/2f64;
         Quaternion{
-            x: (a[[2,1]] - a[[1,2]])/(4f64*qw),
-            y: (a[[0,2]] - a[[2,0]])/(4f64*qw), 
-            z: (a[[1,0]] - a[[0,1]])/(4f64*qw), 
+            x: (a[[1,2]] - a[[2,1]])/(4f64*qw),
+            y: (a[[2,0]] - a[[0,2]])/(4f64*qw), 
+            z: (a[[0,1]] - a[[1,0]])/(4f64*qw), 
             w: qw
         }
     }
     else if a[[0,0]] > a[[1,1]] && a[[0,0]] > a[[2,2]] {
         let s = 2.0f64 * (1.0f64 + a[[0,0]] - a[[1,1]] - a[[2,2]]).sqrt();
         Quaternion{
-            w: (a[[2,1]] - a[[1,2]] ) / s,
+            w: (a[[1,2]] - a[[2,1]] ) / s,
             x: 0.25f64 * s,
             y: (a[[0,1]] + a[[1,0]] ) / s,
             z: (a[[0,2]] + a[[2,0]] ) / s
@@ -52,7 +53,7 @@ pub fn quaternion_from_transform_matrix(a: &Array2<f64>) ->  Quaternion {
     } else if a[[1,1]] > a[[2,2]] {
         let s = 2.0f64 * (1.0f64 + a[[1,1]] - a[[0,0]] - a[[2,2]]).sqrt();
         Quaternion{
-            w: (a[[0,2]] - a[[2,0]] ) / s,
+            w: (a[[2,0]] - a[[0,2]] ) / s,
             x: (a[[0,1]] + a[[1,0]] ) / s,
             y: 0.25f64 * s,
             z: (a[[1,2]] + a[[2,1]] ) / s
@@ -60,7 +61,7 @@ pub fn quaternion_from_transform_matrix(a: &Array2<f64>) ->  Quaternion {
     } else {
         let s = 2.0f64 * (1.0f64 + a[[2,2]] - a[[0,0]] - a[[1,1]]).sqrt();
         Quaternion{
-            w: (a[[1,0]] - a[[0,1]] ) / s,
+            w: (a[[0,1]] - a[[1,0]] ) / s,
             x: (a[[0,2]] + a[[2,0]] ) / s,
             y: (a[[1,2]] + a[[2,1]] ) / s,
             z: 0.25f64 * s
@@ -68,10 +69,11 @@ pub fn quaternion_from_transform_matrix(a: &Array2<f64>) ->  Quaternion {
     }
 }
 
-/// Converts a quaternion to an SE3 matrix
+/// Converts a quaternion to an SE3 matrix. Points are row vectors multiplied from the left, so the matrix holds the
+/// transpose of the rotation and the translation goes in the bottom row.
 pub fn transform_matrix_from_quaternion(msg: &Quaternion) ->  Array2<f64> {
-    let s  = (msg.x*msg.x+ msg.y*msg.y + msg.z*msg.z + msg.w*msg.w).sqrt();
-    let a =  arr2(&[[1f64 - 2f64*s*(msg.y*msg.y + msg.z*msg.z), 2f64*s*(msg.x*msg.y - msg.z*msg.w), 2f64*s*(msg.x*msg.z + msg.y*msg.w), 0f64],
+    let s  = 1f64 / (msg.x*msg.x+ msg.y*msg.y + msg.z*msg.z + msg.w*msg.w);
+    let a =  arr2(&[[1f64 - 2f64*s*(msg.y*msg.y + msg.z*msg.z), 2f64*s*(msg.x*msg.y + msg.z*msg.w), 2f64*s*(msg.x*msg.z - msg.y*msg.w), 0f64],
                     [2f64*s*(msg.x*msg.y - msg.w*msg.z), 1f64 - 2f64*s*(msg.x*msg.x + msg.z*msg.z), 2f64*s*(msg.y*msg.z + msg.w*msg.x), 0f64],
                     [2f64*s*(msg.x*msg.z+ msg.w*msg.y), 2f64*s*(msg.y*msg.z-msg.w*msg.x), 1f64 - 2f64*s*(msg.x*msg.x + msg.y*msg.y), 0f64],
                     [0f64, 0f64, 0f64, 1f64]]);
@@ -116,50 +118,129 @@ pub fn chain_transforms(transforms: &Vec<Transform>) -> Transform {
     transform_from_se3(&final_transform)
 }
 
+/// Interpolates between two transforms. `weight` is the fraction of the way from `t1` to `t2`, so a weight of 0
+/// gives `t1` and a weight of 1 gives `t2`. The same weight is applied to the translation, which is interpolated 
+/// linearly, and to the rotation, which is interpolated along the shortest great arc (slerp).
 pub fn interpolate(t1: Transform, t2: Transform, weight: f64) -> Transform {
-    let r1 = geometry::Quaternion::new(t1.orientation.w, t1.orientation.x, t1.orientation.y, t1.orientation.z);
-    let r2 = geometry::Quaternion::new(t2.orientation.w, t2.orientation.x, t2.orientation.y, t2.orientation.z);
-    let r1 = geometry::UnitQuaternion::from_quaternion(r1);
-    let r2 = geometry::UnitQuaternion::from_quaternion(r2);
-    let res  = r1.try_slerp(&r2, weight, 1e-9);
-    match res {
-        Some(qt) => {
-            Transform{
-                position: Position {
-                    x: t1.position.x * weight + t2.position.x * (1.0 - weight),
-                    y: t1.position.y * weight + t2.position.y * (1.0 - weight),
-                    z: t1.position.z * weight + t2.position.z * (1.0 - weight) 
-                },
-                orientation: Quaternion {
-                    x: qt.coords[0],
-                    y: qt.coords[1],
-                    z: qt.coords[2],
-                    w: qt.coords[3]
-                }
-            } 
-        }
-        None => {
-            if weight > 0.5 {
-                Transform{
-                    position: Position {
-                        x: t1.position.x * weight + t2.position.x * (1.0 - weight),
-                        y: t1.position.y * weight + t2.position.y * (1.0 - weight),
-                        z: t1.position.z * weight + t2.position.z * (1.0 - weight) 
-                    },
-                    orientation: t1.orientation.clone()
-                }
-            }
-            else {
-                Transform{
-                    position: Position {
-                        x: t1.position.x * weight + t2.position.x * (1.0 - weight),
-                        y: t1.position.y * weight + t2.position.y * (1.0 - weight),
-                        z: t1.position.z * weight + t2.position.z * (1.0 - weight) 
-                    },
-                    orientation: t2.orientation.clone()
-                }
-            }
-        } 
+    let r1 = to_unit_quaternion(&t1.orientation);
+    let r2 = to_unit_quaternion(&t2.orientation);
+    let qt = slerp(&r1, &r2, weight);
+    Transform{
+        position: Position {
+            x: t1.position.x * (1.0 - weight) + t2.position.x * weight,
+            y: t1.position.y * (1.0 - weight) + t2.position.y * weight,
+            z: t1.position.z * (1.0 - weight) + t2.position.z * weight
+        },
+        orientation: from_unit_quaternion(&qt)
+    }
+}
+
+/// Extrapolates from `t1` through `t2` assuming constant linear and angular velocity. `ratio` is the desired 
+/// time offset from `t1` divided by the time between `t1` and `t2`, so 0 gives `t1` and 1 gives `t2`.
+pub fn extrapolate(t1: Transform, t2: Transform, ratio: f64) -> Transform {
+    let r1 = to_unit_quaternion(&t1.orientation);
+    let r2 = to_unit_quaternion(&t2.orientation);
+    // Rotation from t1 to t2, scaled by the ratio. powf takes the shortest path.
+    let delta = r2 * r1.inverse();
+    let qt = delta.powf(ratio) * r1;
+    Transform{
+        position: Position {
+            x: t1.position.x + (t2.position.x - t1.position.x) * ratio,
+            y: t1.position.y + (t2.position.y - t1.position.y) * ratio,
+            z: t1.position.z + (t2.position.z - t1.position.z) * ratio
+        },
+        orientation: from_unit_quaternion(&qt)
+    }
+}
+
+/// Interpolates with a cubic spline through four consecutive samples, each given as a time in seconds and a transform. 
+/// The requested `time` must lie between the second and third samples. At the ends of the data, the missing neighbour
+/// should be a copy of the closest sample, which makes the spline fall back to a one sided tangent.
+///
+/// Translation uses a cubic Hermite spline with finite difference tangents which account for uneven sample spacing. 
+/// Rotation uses spherical quadrangle interpolation (SQUAD) which is the rotational equivalent of a cubic spline.
+pub fn cubic_interpolate(samples: &[(f64, Transform); 4], time: f64) -> Transform {
+    let (t0, tf0) = samples[0];
+    let (t1, tf1) = samples[1];
+    let (t2, tf2) = samples[2];
+    let (t3, tf3) = samples[3];
+    let h = t2 - t1;
+    let u = (time - t1) / h;
+
+    // Tangents with respect to u, the fraction of the way from the second to the third sample
+    let tangent = |p_before: f64, p_after: f64, span: f64| (p_after - p_before) * h / span;
+    let h00 = 2.0*u*u*u - 3.0*u*u + 1.0;
+    let h10 = u*u*u - 2.0*u*u + u;
+    let h01 = -2.0*u*u*u + 3.0*u*u;
+    let h11 = u*u*u - u*u;
+    let hermite = |p0: f64, p1: f64, p2: f64, p3: f64| {
+        h00 * p1 + h10 * tangent(p0, p2, t2 - t0) + h01 * p2 + h11 * tangent(p1, p3, t3 - t1)
+    };
+
+    let q0 = to_unit_quaternion(&tf0.orientation);
+    let q1 = to_unit_quaternion(&tf1.orientation);
+    let q2 = same_hemisphere(&q1, to_unit_quaternion(&tf2.orientation));
+    let q3 = same_hemisphere(&q2, to_unit_quaternion(&tf3.orientation));
+    let q0 = same_hemisphere(&q1, q0);
+    let s1 = squad_control_point(&q0, &q1, &q2);
+    let s2 = squad_control_point(&q1, &q2, &q3);
+    let qt = slerp(&slerp(&q1, &q2, u), &slerp(&s1, &s2, u), 2.0 * u * (1.0 - u));
+
+    Transform {
+        position: Position {
+            x: hermite(tf0.position.x, tf1.position.x, tf2.position.x, tf3.position.x),
+            y: hermite(tf0.position.y, tf1.position.y, tf2.position.y, tf3.position.y),
+            z: hermite(tf0.position.z, tf1.position.z, tf2.position.z, tf3.position.z)
+        },
+        orientation: from_unit_quaternion(&qt)
+    }
+}
+
+/// Intermediate control point of SQUAD at `current`, given its neighbours.
+fn squad_control_point(previous: &geometry::UnitQuaternion<f64>, current: &geometry::UnitQuaternion<f64>, 
+    next: &geometry::UnitQuaternion<f64>) -> geometry::UnitQuaternion<f64> {
+    // The logarithm of a unit quaternion is half its scaled axis, and the exponential its inverse.
+    let inverse = current.inverse();
+    let to_next = (inverse * next).scaled_axis();
+    let to_previous = (inverse * previous).scaled_axis();
+    current * geometry::UnitQuaternion::from_scaled_axis((to_next + to_previous) * -0.25)
+}
+
+/// Flips the sign of `q` if needed so that it is within 90 degrees of `reference` in quaternion space. Both signs 
+/// represent the same rotation, but interpolating between quaternions of opposite sign takes the long way round.
+fn same_hemisphere(reference: &geometry::UnitQuaternion<f64>, q: geometry::UnitQuaternion<f64>) -> geometry::UnitQuaternion<f64> {
+    if reference.coords.dot(&q.coords) < 0.0 {
+        geometry::UnitQuaternion::new_unchecked(-q.into_inner())
+    }
+    else {
+        q
+    }
+}
+
+/// Spherical linear interpolation which always takes the shortest path. Falls back to normalised linear 
+/// interpolation when the quaternions are too close for slerp to be numerically stable.
+fn slerp(q1: &geometry::UnitQuaternion<f64>, q2: &geometry::UnitQuaternion<f64>, t: f64) -> geometry::UnitQuaternion<f64> {
+    let q2 = same_hemisphere(q1, *q2);
+    let theta = q1.coords.dot(&q2.coords).min(1.0).acos();
+    let sin_theta = theta.sin();
+    if sin_theta < 1e-9 {
+        return geometry::UnitQuaternion::new_normalize(geometry::Quaternion::from(q1.coords * (1.0 - t) + q2.coords * t));
+    }
+    let w1 = ((1.0 - t) * theta).sin() / sin_theta;
+    let w2 = (t * theta).sin() / sin_theta;
+    geometry::UnitQuaternion::new_normalize(geometry::Quaternion::from(q1.coords * w1 + q2.coords * w2))
+}
+
+fn to_unit_quaternion(q: &Quaternion) -> geometry::UnitQuaternion<f64> {
+    geometry::UnitQuaternion::from_quaternion(geometry::Quaternion::new(q.w, q.x, q.y, q.z))
+}
+
+fn from_unit_quaternion(q: &geometry::UnitQuaternion<f64>) -> Quaternion {
+    Quaternion {
+        x: q.coords[0],
+        y: q.coords[1],
+        z: q.coords[2],
+        w: q.coords[3]
     }
 }
 
@@ -219,6 +300,21 @@ mod test {
         assert_eq!(qt, q2);
     }
 
+    #[test]
+    fn test_quaternion_quarter_turn(){
+        let half_angle = std::f64::consts::FRAC_PI_4;
+        let qt = Quaternion{
+            x: 0f64,
+            y: half_angle.sin(),
+            z: 0f64,
+            w: half_angle.cos()
+        };
+        let arr = transform_matrix_from_quaternion(&qt);
+        let q2 = quaternion_from_transform_matrix(&arr);
+        assert!((qt.y - q2.y).abs() < 1e-9 && (qt.w - q2.w).abs() < 1e-9);
+        assert!(q2.x.abs() < 1e-9 && q2.z.abs() < 1e-9);
+    }
+
     #[test]
     fn test_basic_translation_chaining(){
         let tf1 = Transform {
@@ -250,4 +346,102 @@ mod test {
         };
         assert_eq!(interpolate(tf1, tf2, 0.5), expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_constant_velocity_extrapolation() {
+        let tf1 = Transform {
+            position: Position{x: 1f64, y: 1f64, z: 0f64},
+            orientation: Quaternion{x: 0f64, y: 0f64, z: 0f64, w: 1f64}
+        };
+        let tf2 = Transform {
+            position: Position{x: 2f64, y: 2f64, z: 0f64},
+            orientation: Quaternion{x: 0f64, y: 0f64, z: (std::f64::consts::PI/8f64).sin(), w: (std::f64::consts::PI/8f64).cos()}
+        };
+        let res = extrapolate(tf1, tf2, 2f64);
+        assert!((res.position.x - 3f64).abs() < 1e-9);
+        assert!((res.position.y - 3f64).abs() < 1e-9);
+        assert!((res.orientation.z - (std::f64::consts::PI/4f64).sin()).abs() < 1e-9);
+        assert!((res.orientation.w - (std::f64::consts::PI/4f64).cos()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cubic_interpolation_of_linear_motion() {
+        let tf = |x: f64| Transform {
+            position: Position{x, y: 2f64 * x, z: 0f64},
+            orientation: Quaternion{x: 0f64, y: 0f64, z: (x/4f64).sin(), w: (x/4f64).cos()}
+        };
+        let samples = [(0f64, tf(0f64)), (1f64, tf(1f64)), (2f64, tf(2f64)), (3f64, tf(3f64))];
+        let res = cubic_interpolate(&samples, 1.5f64);
+        let expected = tf(1.5f64);
+        assert!((res.position.x - expected.position.x).abs() < 1e-9);
+        assert!((res.position.y - expected.position.y).abs() < 1e-9);
+        assert!((res.orientation.z - expected.orientation.z).abs() < 1e-9);
+        assert!((res.orientation.w - expected.orientation.w).abs() < 1e-9);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn arb_transform() -> impl Strategy<Value = Transform> {
+        (-100.0..100.0f64, -100.0..100.0f64, -100.0..100.0f64, -1.0..1.0f64, -1.0..1.0f64, -1.0..1.0f64, -1.0..1.0f64)
+            .prop_filter("quaternion must be normalisable", |(_, _, _, x, y, z, w)| (x*x + y*y + z*z + w*w).sqrt() > 0.1)
+            .prop_map(|(px, py, pz, x, y, z, w)| {
+                let norm = (x*x + y*y + z*z + w*w).sqrt();
+                Transform {
+                    position: Position{x: px, y: py, z: pz},
+                    orientation: Quaternion{x: x/norm, y: y/norm, z: z/norm, w: w/norm}
+                }
+            })
+    }
+
+    fn negated(t: Transform) -> Transform {
+        Transform {
+            position: t.position,
+            orientation: Quaternion{x: -t.orientation.x, y: -t.orientation.y, z: -t.orientation.z, w: -t.orientation.w}
+        }
+    }
+
+    /// Angle of the rotation between two orientations. Quaternions of opposite sign are the same rotation.
+    fn rotation_angle(q1: &Quaternion, q2: &Quaternion) -> f64 {
+        let dot = (q1.x*q2.x + q1.y*q2.y + q1.z*q2.z + q1.w*q2.w).abs().min(1.0);
+        2.0 * dot.acos()
+    }
+
+    fn assert_same_transform(t1: &Transform, t2: &Transform) -> Result<(), TestCaseError> {
+        prop_assert!((t1.position.x - t2.position.x).abs() < EPSILON);
+        prop_assert!((t1.position.y - t2.position.y).abs() < EPSILON);
+        prop_assert!((t1.position.z - t2.position.z).abs() < EPSILON);
+        prop_assert!(rotation_angle(&t1.orientation, &t2.orientation) < 1e-6);
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn interpolation_matches_endpoints(t1 in arb_transform(), t2 in arb_transform()) {
+            assert_same_transform(&interpolate(t1, t2, 0.0), &t1)?;
+            assert_same_transform(&interpolate(t1, t2, 1.0), &t2)?;
+        }
+
+        #[test]
+        fn interpolation_is_symmetric(t1 in arb_transform(), t2 in arb_transform(), weight in 0.0..1.0f64) {
+            assert_same_transform(&interpolate(t1, t2, weight), &interpolate(t2, t1, 1.0 - weight))?;
+            assert_same_transform(&interpolate(t1, t2, 0.5), &interpolate(t2, t1, 0.5))?;
+        }
+
+        #[test]
+        fn interpolation_takes_shortest_path(t1 in arb_transform(), t2 in arb_transform(), weight in 0.0..1.0f64) {
+            let res = interpolate(t1, t2, weight);
+            let total = rotation_angle(&t1.orientation, &t2.orientation);
+            prop_assert!(total <= std::f64::consts::PI + EPSILON);
+            prop_assert!((rotation_angle(&t1.orientation, &res.orientation) - weight * total).abs() < 1e-6);
+            prop_assert!((rotation_angle(&res.orientation, &t2.orientation) - (1.0 - weight) * total).abs() < 1e-6);
+            // Flipping the sign of a quaternion does not change the rotation, so it must not change the result
+            assert_same_transform(&interpolate(t1, negated(t2), weight), &res)?;
+        }
+    }
+}